@@ -1,8 +1,16 @@
-use crate::config::Target;
+use crate::config::{ResolveState, Target, TargetDefaults};
 use crate::logging::SessionLogger;
-use crate::ping::{PingUpdate, spawn_pinger};
-use crate::stats::{PingResult, TargetStats};
+use crate::metrics::MetricsServer;
+use crate::ping::{IcmpClients, PingUpdate, PingerHandle, UpdateKind, spawn_pinger};
+use crate::probe::TargetSpec;
+use crate::push::PushSink;
+use crate::resolve::{expand_targets, is_hostname};
+use crate::stats::{BucketBounds, TargetStats};
+use crate::trace::Trace;
 use chrono::{DateTime, Utc};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
@@ -15,6 +23,167 @@ pub enum ViewMode {
     Detail,
 }
 
+/// A row of the list view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListRow {
+    /// Aggregate header of a target group.
+    Group(String),
+    /// A target, by index.
+    Target(usize),
+}
+
+/// Probe intervals offered when speeding up or slowing down, in milliseconds.
+const INTERVAL_STEPS_MS: [u64; 10] = [50, 100, 200, 500, 1000, 2000, 5000, 10_000, 30_000, 60_000];
+
+/// Returns the next faster (or slower) interval step from `current`.
+fn step_interval(current: Duration, faster: bool) -> Duration {
+    let ms = current.as_millis() as u64;
+    let step = if faster {
+        INTERVAL_STEPS_MS.iter().rev().find(|&&s| s < ms)
+    } else {
+        INTERVAL_STEPS_MS.iter().find(|&&s| s > ms)
+    };
+    step.map_or(current, |&s| Duration::from_millis(s))
+}
+
+/// Text prompt shown in place of the list view footer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prompt {
+    /// Editing the filter (typed into `App::filter` directly).
+    Filter,
+    /// Target to add, and why the last attempt was rejected.
+    AddTarget {
+        input: String,
+        error: Option<String>,
+    },
+    /// Confirming removal of a target.
+    RemoveTarget(usize),
+}
+
+/// Returns active target indices in display order: each group is gathered
+/// where its first member appears; ungrouped targets keep their position.
+pub fn display_order(targets: &[Target]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(targets.len());
+    let mut placed = HashSet::new();
+    for (idx, target) in targets.iter().enumerate() {
+        if !target.active {
+            continue;
+        }
+        match &target.group {
+            None => order.push(idx),
+            Some(group) if placed.insert(group) => order.extend(
+                (idx..targets.len())
+                    .filter(|&i| targets[i].active && targets[i].group.as_ref() == Some(group)),
+            ),
+            Some(_) => {}
+        }
+    }
+    order
+}
+
+/// Column the target list can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Avg,
+    P95,
+    Loss,
+    Grade,
+    Jitter,
+}
+
+impl SortKey {
+    /// Returns the column label.
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Avg => "Avg",
+            SortKey::P95 => "P95",
+            SortKey::Loss => "Loss",
+            SortKey::Grade => "Grade",
+            SortKey::Jitter => "Jitter",
+        }
+    }
+
+    /// Returns the key after `key` when cycling, ending with unsorted.
+    fn cycle(key: Option<SortKey>) -> Option<SortKey> {
+        match key {
+            None => Some(SortKey::Avg),
+            Some(SortKey::Avg) => Some(SortKey::P95),
+            Some(SortKey::P95) => Some(SortKey::Loss),
+            Some(SortKey::Loss) => Some(SortKey::Grade),
+            Some(SortKey::Grade) => Some(SortKey::Jitter),
+            Some(SortKey::Jitter) => None,
+        }
+    }
+
+    /// Returns the recent-window value to sort by, where larger is worse, or
+    /// None without data.
+    fn value(self, stats: &TargetStats) -> Option<f64> {
+        match self {
+            SortKey::Avg => stats.average().map(|d| d.as_secs_f64()),
+            SortKey::P95 => stats.p95().map(|d| d.as_secs_f64()),
+            SortKey::Loss => (stats.window_count() > 0).then(|| stats.window_packet_loss().1),
+            SortKey::Grade => stats.mos_score().map(|mos| -mos),
+            SortKey::Jitter => stats.jitter().map(|d| d.as_secs_f64()),
+        }
+    }
+}
+
+/// Sorts a display order by `key`, worst first when `descending`. Groups
+/// stay together, sorted internally and placed by their first member;
+/// targets without data go last and ties keep their order.
+pub fn sort_order(
+    order: &[usize],
+    targets: &[Target],
+    stats: &[TargetStats],
+    key: SortKey,
+    descending: bool,
+) -> Vec<usize> {
+    let compare = |a: &usize, b: &usize| match (key.value(&stats[*a]), key.value(&stats[*b])) {
+        (Some(a), Some(b)) if descending => b.total_cmp(&a),
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+
+    // Split into runs of one group or a single ungrouped target
+    let mut units: Vec<Vec<usize>> = Vec::new();
+    for &idx in order {
+        let group = targets[idx].group.as_ref();
+        match units.last_mut() {
+            Some(unit) if group.is_some() && targets[unit[0]].group.as_ref() == group => {
+                unit.push(idx)
+            }
+            _ => units.push(vec![idx]),
+        }
+    }
+    for unit in &mut units {
+        unit.sort_by(compare);
+    }
+    units.sort_by(|a, b| compare(&a[0], &b[0]));
+    units.concat()
+}
+
+/// Returns the list view rows for a display order: a header before each
+/// group's members, which are left out while the group is collapsed.
+pub fn list_rows(targets: &[Target], order: &[usize], collapsed: &HashSet<String>) -> Vec<ListRow> {
+    let mut rows = Vec::with_capacity(order.len());
+    let mut current: Option<&String> = None;
+    for &idx in order {
+        let group = targets[idx].group.as_ref();
+        if let Some(name) = group
+            && group != current
+        {
+            rows.push(ListRow::Group(name.clone()));
+        }
+        current = group;
+        if !group.is_some_and(|g| collapsed.contains(g)) {
+            rows.push(ListRow::Target(idx));
+        }
+    }
+    rows
+}
+
 /// Main application state.
 pub struct App {
     /// List of targets being pinged.
@@ -23,14 +192,57 @@ pub struct App {
     pub stats: Vec<TargetStats>,
     /// Currently selected row (for future keyboard nav).
     pub selected: usize,
+    /// Group whose header row is under the cursor, if any (otherwise the
+    /// cursor is on `selected`).
+    pub selected_group: Option<String>,
+    /// Groups shown as a header row only.
+    pub collapsed: HashSet<String>,
+    /// First row shown in the table (scroll position).
+    pub scroll: usize,
+    /// Number of rows that fit in the table, updated on each draw.
+    pub page_size: usize,
+    /// Show one row per target instead of two.
+    pub compact: bool,
+    /// Column the list is sorted by (configuration order when None).
+    pub sort: Option<SortKey>,
+    /// Sort worst first.
+    pub sort_descending: bool,
+    /// Only list targets whose name or address contains this.
+    pub filter: String,
+    /// Prompt taking keyboard input, if open.
+    pub prompt: Option<Prompt>,
     /// Whether the app should quit.
     pub should_quit: bool,
     /// Current view mode.
     pub view_mode: ViewMode,
     /// Channel receiver for ping updates.
     rx: mpsc::UnboundedReceiver<PingUpdate>,
+    /// Sender side of the update channel, for tasks started later.
+    tx: mpsc::UnboundedSender<PingUpdate>,
+    /// Rows of targets added at runtime with `all_addrs`, once resolved.
+    expanded_rx: mpsc::UnboundedReceiver<Vec<Target>>,
+    /// Sender side of the expanded rows channel.
+    expanded_tx: mpsc::UnboundedSender<Vec<Target>>,
+    /// Global ping interval.
+    interval: Duration,
+    /// Hostname re-resolution interval.
+    resolve_interval: Option<Duration>,
+    /// Settings for targets added at runtime.
+    defaults: TargetDefaults,
+    /// Latency histogram buckets.
+    buckets: BucketBounds,
+    /// ICMP sockets shared by the pingers.
+    clients: Arc<IcmpClients>,
+    /// Pinger tasks, by target index.
+    pingers: Vec<PingerHandle>,
+    /// Running hop discoveries, by target index.
+    pub traces: HashMap<usize, Trace>,
     /// Session logger.
     pub logger: SessionLogger,
+    /// Metrics endpoint, if enabled.
+    pub metrics: Option<MetricsServer>,
+    /// StatsD/InfluxDB push output, if enabled.
+    pub push: Option<PushSink>,
     /// Session start time.
     pub started_at: DateTime<Utc>,
 }
@@ -39,50 +251,139 @@ impl App {
     /// Creates a new App and starts pinging all targets.
     pub fn new(
         targets: Vec<Target>,
+        defaults: TargetDefaults,
         interval: Duration,
-        log_raw: bool,
-        log_summary: bool,
+        resolve_interval: Option<Duration>,
+        buckets: BucketBounds,
+        mut logger: SessionLogger,
     ) -> anyhow::Result<Self> {
         let (tx, rx) = mpsc::unbounded_channel();
+        let (expanded_tx, expanded_rx) = mpsc::unbounded_channel();
+
+        let stats: Vec<TargetStats> = targets
+            .iter()
+            .map(|_| TargetStats::with_buckets(buckets.clone()))
+            .collect();
 
-        let stats: Vec<TargetStats> = targets.iter().map(|_| TargetStats::new()).collect();
+        // Spawn a pinger for each target, sharing the ICMP sockets
+        let clients = Arc::new(IcmpClients::new());
+        let pingers = targets
+            .iter()
+            .enumerate()
+            .map(|(idx, target)| {
+                let target_interval = target.interval.unwrap_or(interval);
+                spawn_pinger(
+                    idx,
+                    target.clone(),
+                    target_interval,
+                    resolve_interval,
+                    clients.clone(),
+                    tx.clone(),
+                )
+            })
+            .collect();
 
-        // Spawn a pinger for each target
         for (idx, target) in targets.iter().enumerate() {
-            spawn_pinger(idx, target.clone(), interval, tx.clone());
+            let _ = logger.log_target_info(idx, target);
         }
-
-        let logger = SessionLogger::new(log_raw, log_summary)?;
         let started_at = logger.started;
 
         Ok(Self {
             targets,
             stats,
             selected: 0,
+            selected_group: None,
+            collapsed: HashSet::new(),
+            scroll: 0,
+            page_size: 1,
+            compact: false,
+            sort: None,
+            sort_descending: true,
+            filter: String::new(),
+            prompt: None,
             should_quit: false,
             view_mode: ViewMode::List,
             rx,
+            tx,
+            expanded_rx,
+            expanded_tx,
+            interval,
+            resolve_interval,
+            defaults,
+            buckets,
+            clients,
+            pingers,
+            traces: HashMap::new(),
             logger,
+            metrics: None,
+            push: None,
             started_at,
         })
     }
 
     /// Processes any pending ping updates.
     pub fn process_updates(&mut self) {
+        while let Ok(rows) = self.expanded_rx.try_recv() {
+            let mut first = None;
+            for row in rows {
+                first.get_or_insert(self.start_target(row));
+            }
+            if let Some(idx) = first {
+                self.set_cursor(ListRow::Target(idx));
+            }
+        }
+
         while let Ok(update) = self.rx.try_recv() {
-            if update.target_idx < self.stats.len() {
-                // Log the ping event
-                let latency = match &update.result {
-                    PingResult::Success(d) => Some(*d),
-                    _ => None,
-                };
-                let _ = self.logger.log_ping(
-                    update.target_idx,
-                    &self.targets[update.target_idx],
-                    latency,
-                );
-
-                self.stats[update.target_idx].record(update.result);
+            let idx = update.target_idx;
+            // Updates sent before a target was removed are dropped
+            if idx >= self.stats.len() || !self.targets[idx].active {
+                continue;
+            }
+
+            match update.kind {
+                UpdateKind::Ping(result) => {
+                    // Log the ping event
+                    let _ = self.logger.log_ping(idx, &self.targets[idx], &result);
+                    if let Some(push) = &mut self.push {
+                        push.ping(&self.targets[idx], &result);
+                    }
+
+                    self.stats[idx].record(result);
+                }
+                UpdateKind::Resolved(addr) => {
+                    let previous = self.targets[idx].addr();
+                    self.targets[idx].state = ResolveState::Resolved(addr);
+                    if let Some(previous) = previous
+                        && previous != addr
+                    {
+                        let _ = self
+                            .logger
+                            .log_address_change(idx, &self.targets[idx], previous);
+                        self.stats[idx].record_addr_change(previous, addr);
+                    }
+                }
+                UpdateKind::ResolveFailed(err) => {
+                    self.targets[idx].state = ResolveState::Unresolved(err);
+                }
+                UpdateKind::Sequence(event) => {
+                    self.stats[idx].record_sequence_event(event);
+                }
+                UpdateKind::Phases(phases) => {
+                    self.stats[idx].phases.record(phases);
+                }
+                UpdateKind::Hop(reply) => {
+                    if let Some(trace) = self.traces.get_mut(&idx) {
+                        trace.record(reply);
+                    }
+                }
+                UpdateKind::TraceFailed(err) => {
+                    if let Some(trace) = self.traces.get_mut(&idx) {
+                        trace.error = Some(err);
+                    }
+                }
+                UpdateKind::Pmtu(pmtu) => {
+                    self.stats[idx].pmtu = Some(pmtu);
+                }
             }
         }
 
@@ -90,6 +391,15 @@ impl App {
         let _ = self
             .logger
             .maybe_write_periodic_summary(&self.targets, &self.stats);
+        if let Some(stream) = &mut self.logger.stream {
+            let _ = stream.maybe_write_aggregates(&self.targets, &self.stats);
+        }
+        if let Some(push) = &mut self.push {
+            push.maybe_push_aggregates(&self.targets, &self.stats);
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.answer(&self.targets, &self.stats);
+        }
     }
 
     /// Returns session elapsed time.
@@ -97,20 +407,371 @@ impl App {
         Utc::now().signed_duration_since(self.started_at)
     }
 
+    /// Returns the indices of the targets passing the filter, grouped and
+    /// sorted for display.
+    pub fn order(&self) -> Vec<usize> {
+        let order: Vec<usize> = display_order(&self.targets)
+            .into_iter()
+            .filter(|&idx| self.filter.is_empty() || self.targets[idx].matches(&self.filter))
+            .collect();
+        match self.sort {
+            Some(key) => sort_order(
+                &order,
+                &self.targets,
+                &self.stats,
+                key,
+                self.sort_descending,
+            ),
+            None => order,
+        }
+    }
+
+    /// Returns the rows of the list view, in display order.
+    pub fn rows(&self) -> Vec<ListRow> {
+        list_rows(&self.targets, &self.order(), &self.collapsed)
+    }
+
+    /// Returns the row under the cursor.
+    fn cursor(&self) -> ListRow {
+        match &self.selected_group {
+            Some(group) => ListRow::Group(group.clone()),
+            None => ListRow::Target(self.selected),
+        }
+    }
+
+    /// Moves the cursor to `row`.
+    fn set_cursor(&mut self, row: ListRow) {
+        match row {
+            ListRow::Group(group) => self.selected_group = Some(group),
+            ListRow::Target(idx) => {
+                self.selected = idx;
+                self.selected_group = None;
+            }
+        }
+    }
+
+    /// Moves the cursor to the row returned by `step(position, len)`. The
+    /// detail view steps through every target, skipping group headers.
+    fn move_cursor(&mut self, step: impl Fn(usize, usize) -> usize) {
+        let rows = match self.view_mode {
+            ViewMode::List => self.rows(),
+            ViewMode::Detail => self.order().into_iter().map(ListRow::Target).collect(),
+        };
+        if rows.is_empty() {
+            return;
+        }
+        let position = rows.iter().position(|r| *r == self.cursor()).unwrap_or(0);
+        let next = step(position, rows.len()).min(rows.len() - 1);
+        self.set_cursor(rows[next].clone());
+    }
+
     /// Moves selection up.
     pub fn select_previous(&mut self) {
-        if self.selected > 0 {
-            self.selected -= 1;
-        }
+        self.move_cursor(|pos, _| pos.saturating_sub(1));
     }
 
     /// Moves selection down.
     pub fn select_next(&mut self) {
-        if self.selected < self.targets.len().saturating_sub(1) {
-            self.selected += 1;
+        self.move_cursor(|pos, _| pos + 1);
+    }
+
+    /// Moves selection up by one page.
+    pub fn page_up(&mut self) {
+        let page = self.page_size.max(1);
+        self.move_cursor(|pos, _| pos.saturating_sub(page));
+    }
+
+    /// Moves selection down by one page.
+    pub fn page_down(&mut self) {
+        let page = self.page_size.max(1);
+        self.move_cursor(|pos, _| pos + page);
+    }
+
+    /// Selects the first row.
+    pub fn select_first(&mut self) {
+        self.move_cursor(|_, _| 0);
+    }
+
+    /// Selects the last row.
+    pub fn select_last(&mut self) {
+        self.move_cursor(|_, len| len - 1);
+    }
+
+    /// Switches between one and two table rows per target.
+    pub fn toggle_compact(&mut self) {
+        self.compact = !self.compact;
+    }
+
+    /// Collapses or expands the group under the cursor (or of the selected
+    /// target). Collapsing moves the cursor to the group's header.
+    pub fn toggle_group(&mut self) {
+        let group = match self.cursor() {
+            ListRow::Group(group) => group,
+            ListRow::Target(idx) => match self.targets.get(idx).and_then(|t| t.group.clone()) {
+                Some(group) => group,
+                None => return,
+            },
+        };
+        if !self.collapsed.remove(&group) {
+            self.collapsed.insert(group.clone());
+            self.selected_group = Some(group);
         }
     }
 
+    /// Cycles the sort column: avg, p95, loss, grade, jitter, unsorted. A new
+    /// column starts worst first.
+    pub fn cycle_sort(&mut self) {
+        self.sort = SortKey::cycle(self.sort);
+        self.sort_descending = true;
+    }
+
+    /// Toggles between ascending and descending sort.
+    pub fn toggle_sort_order(&mut self) {
+        self.sort_descending = !self.sort_descending;
+    }
+
+    /// Opens the filter prompt.
+    pub fn start_filter(&mut self) {
+        self.prompt = Some(Prompt::Filter);
+    }
+
+    /// Lists all targets again.
+    pub fn clear_filter(&mut self) {
+        self.filter.clear();
+    }
+
+    /// Opens the prompt for a target to add.
+    pub fn start_add_target(&mut self) {
+        self.prompt = Some(Prompt::AddTarget {
+            input: String::new(),
+            error: None,
+        });
+    }
+
+    /// Asks to confirm removal of the selected target.
+    pub fn start_remove_target(&mut self) {
+        if self.selected_group.is_none() && self.rows().contains(&self.cursor()) {
+            self.prompt = Some(Prompt::RemoveTarget(self.selected));
+        }
+    }
+
+    /// Handles a character typed into the open prompt.
+    pub fn prompt_input(&mut self, c: char) {
+        match &mut self.prompt {
+            Some(Prompt::Filter) => self.filter.push(c),
+            Some(Prompt::AddTarget { input, error }) => {
+                input.push(c);
+                *error = None;
+            }
+            Some(Prompt::RemoveTarget(_)) => match c {
+                'y' | 'Y' => self.submit_prompt(),
+                'n' | 'N' => self.cancel_prompt(),
+                _ => {}
+            },
+            None => {}
+        }
+    }
+
+    /// Removes the last character typed into the open prompt.
+    pub fn prompt_backspace(&mut self) {
+        match &mut self.prompt {
+            Some(Prompt::Filter) => {
+                self.filter.pop();
+            }
+            Some(Prompt::AddTarget { input, error }) => {
+                input.pop();
+                *error = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies the open prompt: keeps the filter, adds the target (leaving
+    /// the prompt open with the error if it doesn't parse) or removes the
+    /// target.
+    pub fn submit_prompt(&mut self) {
+        match self.prompt.take() {
+            Some(Prompt::AddTarget { input, .. }) => match TargetSpec::parse(&input) {
+                Ok(spec) => {
+                    self.add_target(spec);
+                }
+                Err(e) => {
+                    self.prompt = Some(Prompt::AddTarget {
+                        input,
+                        error: Some(e),
+                    })
+                }
+            },
+            Some(Prompt::RemoveTarget(idx)) => self.remove_target(idx),
+            Some(Prompt::Filter) | None => {}
+        }
+    }
+
+    /// Closes the open prompt without applying it (clearing the filter).
+    pub fn cancel_prompt(&mut self) {
+        if self.prompt.take() == Some(Prompt::Filter) {
+            self.clear_filter();
+        }
+    }
+
+    /// Starts monitoring a new target and selects it. Hostnames are resolved
+    /// by its pinger in the background. With `all_addrs`, a hostname is
+    /// expanded into one row per address like at startup; the rows are
+    /// added by `process_updates` once resolved. Returns the target's index,
+    /// or None while its rows are pending.
+    pub fn add_target(&mut self, spec: TargetSpec) -> Option<usize> {
+        let target = self.defaults.target(spec);
+        if target.all_addrs && is_hostname(&target.host) {
+            let tx = self.expanded_tx.clone();
+            tokio::spawn(async move {
+                let _ = tx.send(expand_targets(vec![target]).await);
+            });
+            return None;
+        }
+        let idx = self.start_target(target);
+        self.set_cursor(ListRow::Target(idx));
+        Some(idx)
+    }
+
+    /// Starts a pinger for a target added at runtime. Returns its index.
+    fn start_target(&mut self, target: Target) -> usize {
+        let idx = self.targets.len();
+        self.pingers.push(spawn_pinger(
+            idx,
+            target.clone(),
+            target.interval.unwrap_or(self.interval),
+            self.resolve_interval,
+            self.clients.clone(),
+            self.tx.clone(),
+        ));
+        let _ = self.logger.log_target_added(idx, &target);
+        self.targets.push(target);
+        self.stats
+            .push(TargetStats::with_buckets(self.buckets.clone()));
+        idx
+    }
+
+    /// Pauses or resumes probing of a target.
+    pub fn set_paused(&mut self, idx: usize, paused: bool) {
+        if !self.targets.get(idx).is_some_and(|t| t.active) || self.stats[idx].paused == paused {
+            return;
+        }
+        self.pingers[idx].set_paused(paused);
+        self.stats[idx].set_paused(paused);
+        let _ = self
+            .logger
+            .log_pause_change(idx, &self.targets[idx], paused);
+    }
+
+    /// Returns the selected target, or every active member of the group
+    /// under the cursor.
+    fn selected_targets(&self) -> Vec<usize> {
+        match self.cursor() {
+            ListRow::Group(group) => (0..self.targets.len())
+                .filter(|&i| {
+                    self.targets[i].active && self.targets[i].group.as_ref() == Some(&group)
+                })
+                .collect(),
+            ListRow::Target(idx) => vec![idx],
+        }
+    }
+
+    /// Pauses or resumes the selected target, or every member of the group
+    /// under the cursor.
+    pub fn toggle_pause(&mut self) {
+        let members = self.selected_targets();
+        let pause = members.iter().any(|&i| !self.stats[i].paused);
+        for idx in members {
+            self.set_paused(idx, pause);
+        }
+    }
+
+    /// Pauses all probing, or resumes it if everything is paused.
+    pub fn toggle_pause_all(&mut self) {
+        let pause = !self.all_paused();
+        for idx in 0..self.targets.len() {
+            self.set_paused(idx, pause);
+        }
+    }
+
+    /// Returns whether every active target is paused (false if there are
+    /// none).
+    pub fn all_paused(&self) -> bool {
+        let mut active = self
+            .targets
+            .iter()
+            .zip(&self.stats)
+            .filter(|(t, _)| t.active)
+            .peekable();
+        active.peek().is_some() && active.all(|(_, s)| s.paused)
+    }
+
+    /// Returns the time between probes of a target.
+    pub fn target_interval(&self, idx: usize) -> Duration {
+        self.targets[idx].interval.unwrap_or(self.interval)
+    }
+
+    /// Probes the selected target (or the members of the group under the
+    /// cursor) more or less often, one step at a time.
+    pub fn change_interval(&mut self, faster: bool) {
+        for idx in self.selected_targets() {
+            let every = step_interval(self.target_interval(idx), faster);
+            self.targets[idx].interval = Some(every);
+            self.pingers[idx].set_interval(every);
+        }
+    }
+
+    /// Steps the global interval and applies it to every target, replacing
+    /// per-target intervals.
+    pub fn change_interval_all(&mut self, faster: bool) {
+        self.interval = step_interval(self.interval, faster);
+        for (target, pinger) in self.targets.iter_mut().zip(&self.pingers) {
+            target.interval = None;
+            pinger.set_interval(self.interval);
+        }
+    }
+
+    /// Stops pinging a target and removes it from the list. The index stays
+    /// reserved so that the other targets keep theirs.
+    pub fn remove_target(&mut self, idx: usize) {
+        if !self.targets.get(idx).is_some_and(|t| t.active) {
+            return;
+        }
+        let rows = self.rows();
+        let position = rows.iter().position(|r| *r == self.cursor());
+
+        self.pingers[idx].abort();
+        self.traces.remove(&idx);
+        let _ = self.logger.log_target_removed(idx, &self.targets[idx]);
+        self.targets[idx].active = false;
+        self.stats[idx] = TargetStats::with_buckets(self.buckets.clone());
+
+        // Keep the cursor at the same position in the list
+        let rows = self.rows();
+        if let (Some(position), Some(last)) = (position, rows.len().checked_sub(1)) {
+            self.set_cursor(rows[position.min(last)].clone());
+        }
+    }
+
+    /// Sets how many rows fit in the table and scrolls it so the cursor
+    /// stays visible. The cursor moves to the first row when its target was
+    /// filtered out.
+    pub fn update_viewport(&mut self, page_size: usize) {
+        let rows = self.rows();
+        let cursor = self.cursor();
+        let position = match rows.iter().position(|r| *r == cursor) {
+            Some(position) => position,
+            None => {
+                if let Some(first) = rows.first() {
+                    self.set_cursor(first.clone());
+                }
+                0
+            }
+        };
+        self.page_size = page_size.max(1);
+        self.scroll = scroll_offset(self.scroll, position, rows.len(), self.page_size);
+    }
+
     /// Signals the app to quit.
     pub fn quit(&mut self) {
         self.should_quit = true;
@@ -121,11 +782,36 @@ impl App {
         for stat in &mut self.stats {
             stat.reset();
         }
+        for trace in self.traces.values_mut() {
+            trace.reset();
+        }
+    }
+
+    /// Starts or stops hop discovery for the selected target.
+    pub fn toggle_trace(&mut self) {
+        if self.traces.remove(&self.selected).is_some() {
+            return;
+        }
+        if let Some(target) = self.targets.get(self.selected)
+            && let Some(addr) = target.addr()
+        {
+            let every = target.interval.unwrap_or(self.interval);
+            let trace = Trace::start(self.selected, addr, every, self.tx.clone());
+            self.traces.insert(self.selected, trace);
+        }
     }
 
-    /// Toggles to detail view for the selected target.
+    /// Returns the hop discovery of the selected target, if running.
+    pub fn selected_trace(&self) -> Option<&Trace> {
+        self.traces.get(&self.selected)
+    }
+
+    /// Toggles to detail view for the selected target (or collapses / expands
+    /// the group under the cursor).
     pub fn show_detail(&mut self) {
-        if !self.targets.is_empty() {
+        if self.selected_group.is_some() {
+            self.toggle_group();
+        } else if self.rows().contains(&self.cursor()) {
             self.view_mode = ViewMode::Detail;
         }
     }
@@ -133,6 +819,15 @@ impl App {
     /// Returns to list view.
     pub fn show_list(&mut self) {
         self.view_mode = ViewMode::List;
+        // The detail view may have stepped into a collapsed group
+        if let Some(group) = self
+            .targets
+            .get(self.selected)
+            .and_then(|t| t.group.as_ref())
+            && self.collapsed.contains(group)
+        {
+            self.selected_group = Some(group.clone());
+        }
     }
 
     /// Returns the currently selected target and its stats.
@@ -144,3 +839,221 @@ impl App {
         }
     }
 }
+
+/// Returns the first row to show so that `selected` is visible in a viewport
+/// of `page_size` rows, moving the current `offset` as little as possible.
+fn scroll_offset(offset: usize, selected: usize, len: usize, page_size: usize) -> usize {
+    let max_offset = len.saturating_sub(page_size);
+    let offset = if selected < offset {
+        selected
+    } else if selected >= offset + page_size {
+        selected + 1 - page_size
+    } else {
+        offset
+    };
+    offset.min(max_offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grouped(groups: &[Option<&str>]) -> Vec<Target> {
+        groups
+            .iter()
+            .enumerate()
+            .map(|(i, g)| {
+                let mut t =
+                    Target::new(format!("t{}", i), format!("192.0.2.{}", i).parse().unwrap());
+                t.group = g.map(String::from);
+                t
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_list_rows_gather_groups() {
+        let targets = grouped(&[Some("LAN"), None, Some("ISP"), Some("LAN")]);
+        assert_eq!(display_order(&targets), vec![0, 3, 1, 2]);
+
+        let order = display_order(&targets);
+        let rows = list_rows(&targets, &order, &HashSet::new());
+        assert_eq!(
+            rows,
+            vec![
+                ListRow::Group("LAN".into()),
+                ListRow::Target(0),
+                ListRow::Target(3),
+                ListRow::Target(1),
+                ListRow::Group("ISP".into()),
+                ListRow::Target(2),
+            ]
+        );
+
+        let collapsed = HashSet::from(["LAN".to_string()]);
+        let rows = list_rows(&targets, &order, &collapsed);
+        assert_eq!(rows[0], ListRow::Group("LAN".into()));
+        assert_eq!(rows[1], ListRow::Target(1));
+        assert_eq!(rows.len(), 4);
+    }
+
+    #[test]
+    fn test_sort_order_keeps_groups_together() {
+        use crate::stats::PingResult;
+
+        let targets = grouped(&[None, Some("LAN"), Some("LAN"), None, None]);
+        let stats: Vec<TargetStats> = [Some(20), Some(5), Some(50), Some(10), None]
+            .iter()
+            .map(|ms| {
+                let mut stats = TargetStats::new();
+                if let Some(ms) = ms {
+                    stats.record(PingResult::Success(Duration::from_millis(*ms)));
+                }
+                stats
+            })
+            .collect();
+        let order = display_order(&targets);
+
+        // Worst first: the LAN group leads with its 50 ms member
+        let worst = sort_order(&order, &targets, &stats, SortKey::Avg, true);
+        assert_eq!(worst, vec![2, 1, 0, 3, 4]);
+
+        // Targets without data stay last either way
+        let best = sort_order(&order, &targets, &stats, SortKey::Avg, false);
+        assert_eq!(best, vec![1, 2, 3, 0, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_targets() {
+        let targets = grouped(&[None, None]);
+        let mut app = App::new(
+            targets,
+            TargetDefaults::default(),
+            Duration::from_secs(60),
+            None,
+            BucketBounds::default(),
+            SessionLogger::new(false, false).unwrap(),
+        )
+        .unwrap();
+
+        let idx = app
+            .add_target(TargetSpec::parse("tcp://192.0.2.9:443").unwrap())
+            .unwrap();
+        assert_eq!(idx, 2);
+        assert_eq!(app.targets[idx].name, "tcp://192.0.2.9:443");
+        assert_eq!(app.selected, idx);
+
+        // Removing keeps the other indices and moves the cursor to the next row
+        app.selected = 1;
+        app.remove_target(1);
+        assert!(!app.targets[1].active);
+        assert_eq!(app.rows(), vec![ListRow::Target(0), ListRow::Target(2)]);
+        assert_eq!(app.selected, 2);
+
+        tokio::task::yield_now().await;
+        assert!(app.pingers[1].is_finished());
+        assert!(!app.pingers[0].is_finished());
+
+        app.toggle_pause_all();
+        assert!(app.all_paused());
+        // With every target removed, nothing is paused
+        app.remove_target(0);
+        app.remove_target(2);
+        assert!(!app.all_paused());
+    }
+
+    #[tokio::test]
+    async fn test_add_target_expands_all_addrs() {
+        let defaults = TargetDefaults {
+            all_addrs: true,
+            family: Some(crate::config::IpFamily::V4),
+            ..Default::default()
+        };
+        let mut app = App::new(
+            grouped(&[None]),
+            defaults,
+            Duration::from_secs(60),
+            None,
+            BucketBounds::default(),
+            SessionLogger::new(false, false).unwrap(),
+        )
+        .unwrap();
+
+        // IP literals have nothing to expand
+        assert_eq!(
+            app.add_target(TargetSpec::parse("192.0.2.9").unwrap()),
+            Some(1)
+        );
+
+        // Hostnames get their rows once resolved
+        assert_eq!(
+            app.add_target(TargetSpec::parse("localhost").unwrap()),
+            None
+        );
+        for _ in 0..100 {
+            app.process_updates();
+            if app.targets.len() > 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let row = &app.targets[2];
+        assert_eq!(row.parent.as_deref(), Some("localhost"));
+        assert!(row.addr().is_some_and(|a| a.is_loopback()));
+        assert_eq!(app.selected, 2);
+    }
+
+    #[tokio::test]
+    async fn test_change_interval_of_group() {
+        let targets = grouped(&[Some("LAN"), None, Some("LAN")]);
+        let mut app = App::new(
+            targets,
+            TargetDefaults::default(),
+            Duration::from_secs(1),
+            None,
+            BucketBounds::default(),
+            SessionLogger::new(false, false).unwrap(),
+        )
+        .unwrap();
+
+        // The cursor starts on the LAN header
+        app.select_first();
+        assert_eq!(app.cursor(), ListRow::Group("LAN".into()));
+        app.change_interval(true);
+        let intervals: Vec<_> = app.targets.iter().map(|t| t.interval).collect();
+        let fast = Some(Duration::from_millis(500));
+        assert_eq!(intervals, vec![fast, None, fast]);
+
+        app.change_interval_all(false);
+        assert_eq!(app.interval, Duration::from_secs(2));
+        assert!(app.targets.iter().all(|t| t.interval.is_none()));
+    }
+
+    #[test]
+    fn test_step_interval() {
+        let ms = Duration::from_millis;
+        assert_eq!(step_interval(ms(1000), true), ms(500));
+        assert_eq!(step_interval(ms(1000), false), ms(2000));
+        // Configured intervals between steps snap to the neighbouring step
+        assert_eq!(step_interval(ms(750), true), ms(500));
+        assert_eq!(step_interval(ms(750), false), ms(1000));
+        // Limits
+        assert_eq!(step_interval(ms(50), true), ms(50));
+        assert_eq!(step_interval(ms(60_000), false), ms(60_000));
+        assert_eq!(step_interval(ms(10), false), ms(50));
+    }
+
+    #[test]
+    fn test_scroll_offset_follows_selection() {
+        // Everything fits
+        assert_eq!(scroll_offset(0, 3, 5, 10), 0);
+        // Selection below the viewport scrolls just far enough
+        assert_eq!(scroll_offset(0, 12, 100, 10), 3);
+        // Selection above the viewport scrolls up to it
+        assert_eq!(scroll_offset(20, 15, 100, 10), 15);
+        // Selection inside keeps the offset
+        assert_eq!(scroll_offset(20, 25, 100, 10), 20);
+        // A taller viewport after resizing doesn't leave empty rows at the end
+        assert_eq!(scroll_offset(95, 99, 100, 20), 80);
+    }
+}
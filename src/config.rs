@@ -1,23 +1,99 @@
+use crate::output::OutputFormat;
+use crate::probe::{
+    Dscp, HttpMethod, MAX_PAYLOAD_SIZE, ProbeKind, ProbeOptions, ProbeOverrides, StatusSet,
+    TargetSpec,
+};
+use crate::push::PushTarget;
+use crate::stats::BucketBounds;
+use anyhow::{Context, Result};
 use clap::Parser;
-use std::net::IpAddr;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
+use toml::Spanned;
+
+/// Default ping interval in milliseconds.
+pub const DEFAULT_INTERVAL_MS: u64 = 1000;
 
 /// Network latency monitor - htop for ping
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// Target hosts to ping (IP addresses or hostnames)
+    /// Target hosts to ping (IP addresses, hostnames, tcp://host:port, udp://host:port,
+    /// http(s):// URLs or dns://server?name=example.com)
+    #[arg(short, long, value_parser = TargetSpec::parse)]
+    pub targets: Vec<TargetSpec>,
+
+    /// Ping interval in milliseconds [default: 1000]
     #[arg(short, long)]
-    pub targets: Vec<String>,
+    pub interval: Option<u64>,
+
+    /// ICMP/UDP payload size in bytes [default: 56]
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(0..=MAX_PAYLOAD_SIZE as u64))]
+    pub size: Option<u64>,
+
+    /// Probe timeout in milliseconds [default: 4000]
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+
+    /// IPv4 TTL / IPv6 hop limit of probes
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=255))]
+    pub ttl: Option<u32>,
+
+    /// DSCP marking of probes (e.g. EF, AF41, CS1, BE or 0-63)
+    #[arg(long, value_parser = Dscp::parse)]
+    pub dscp: Option<Dscp>,
+
+    /// Discover the path MTU of ICMP targets with Don't-Fragment probes (Linux)
+    #[arg(long)]
+    pub pmtu: bool,
+
+    /// Send probes from this local address
+    #[arg(long, value_name = "ADDR")]
+    pub source: Option<IpAddr>,
+
+    /// Send probes through this network interface (Linux)
+    #[arg(short = 'I', long, value_name = "NAME", value_parser = parse_interface)]
+    pub interface: Option<String>,
+
+    /// Ping every resolved address of hostname targets (one row per address)
+    #[arg(long)]
+    pub all_addrs: bool,
+
+    /// Only use IPv4 addresses for hostname targets
+    #[arg(short = '4', long, conflicts_with = "ipv6")]
+    pub ipv4: bool,
 
-    /// Ping interval in milliseconds
-    #[arg(short, long, default_value = "1000")]
-    pub interval: u64,
+    /// Only use IPv6 addresses for hostname targets
+    #[arg(short = '6', long)]
+    pub ipv6: bool,
 
-    /// Include default targets (1.1.1.1, 8.8.8.8, 9.9.9.9)
-    #[arg(short = 'd', long, default_value = "true")]
+    /// HTTP status codes counted as success for http(s) targets, e.g. "200-299,301"
+    #[arg(long, value_name = "CODES", value_parser = StatusSet::parse)]
+    pub http_expect: Option<StatusSet>,
+
+    /// Latency histogram bucket bounds in milliseconds, e.g. "1,5,10,50,100"
+    #[arg(long, value_name = "MS", value_parser = BucketBounds::parse)]
+    pub latency_buckets: Option<BucketBounds>,
+
+    /// Re-resolve hostname targets every N seconds (0 to disable)
+    #[arg(long, value_name = "SECS", default_value = "300")]
+    pub resolve_interval: u64,
+
+    /// Config file with targets and defaults (default: ~/.ptop/config.toml)
+    #[arg(short = 'c', long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Include default targets (1.1.1.1, 8.8.8.8, 9.9.9.9), even if the config file disables them
+    #[arg(short = 'd', long)]
     pub defaults: bool,
 
+    /// Skip the default targets
+    #[arg(long)]
+    pub no_defaults: bool,
+
     /// Skip auto-detection of local gateway
     #[arg(long, default_value = "false")]
     pub no_gateway: bool,
@@ -30,6 +106,38 @@ pub struct Args {
     #[arg(short = 's', long)]
     pub summary: bool,
 
+    /// Run without the TUI and print a summary on exit (for cron, systemd or scripts)
+    #[arg(long, conflicts_with = "replay")]
+    pub headless: bool,
+
+    /// Stop after this many seconds (headless mode)
+    #[arg(long, value_name = "SECS", requires = "headless", value_parser = clap::value_parser!(u64).range(1..))]
+    pub duration: Option<u64>,
+
+    /// Stop once every target has sent this many probes (headless mode)
+    #[arg(long, value_name = "N", requires = "headless", value_parser = clap::value_parser!(u64).range(1..))]
+    pub count: Option<u64>,
+
+    /// Stream every event and periodic per-target aggregates to stdout (headless mode)
+    #[arg(long, value_name = "FORMAT", requires = "headless")]
+    pub output: Option<OutputFormat>,
+
+    /// Seconds between per-target aggregates in the output stream
+    #[arg(long, value_name = "SECS", default_value = "10", requires = "output", value_parser = clap::value_parser!(u64).range(1..))]
+    pub aggregate_interval: u64,
+
+    /// Serve Prometheus/OpenMetrics metrics at http://ADDR/metrics, e.g. 127.0.0.1:9640
+    #[arg(long, value_name = "ADDR", conflicts_with = "replay")]
+    pub metrics_listen: Option<SocketAddr>,
+
+    /// Push metrics over UDP to statsd://HOST[:PORT] or influx://HOST[:PORT] (line protocol)
+    #[arg(long, value_name = "URL", value_parser = PushTarget::parse, conflicts_with = "replay")]
+    pub push: Option<PushTarget>,
+
+    /// Push per-target aggregates every N seconds instead of every ping
+    #[arg(long, value_name = "SECS", requires = "push", value_parser = clap::value_parser!(u64).range(1..))]
+    pub push_interval: Option<u64>,
+
     /// Replay a previously recorded session
     #[arg(long, value_name = "PATH")]
     pub replay: Option<String>,
@@ -45,21 +153,570 @@ pub struct Args {
     /// List available session summaries
     #[arg(long)]
     pub list_sessions: bool,
+
+    /// Print each target's latency histogram added up across session summaries, as JSON
+    #[arg(long, value_name = "PATH", num_args = 1..)]
+    pub merge_sessions: Vec<PathBuf>,
+
+    /// Run a UDP echo responder for udp:// probes instead of monitoring
+    #[arg(long, value_name = "ADDR")]
+    pub udp_echo_server: Option<SocketAddr>,
+}
+
+impl Args {
+    /// Returns the probe options given on the command line.
+    pub fn probe_overrides(&self) -> ProbeOverrides {
+        ProbeOverrides {
+            payload_size: self.size.map(|s| s as usize),
+            timeout_ms: self.timeout,
+            ttl: self.ttl,
+            dscp: self.dscp,
+            pmtu: self.pmtu.then_some(true),
+            source: self.source,
+            interface: self.interface.clone(),
+        }
+    }
+}
+
+/// Longest interface name accepted by the kernel (IFNAMSIZ - 1).
+const MAX_INTERFACE_LEN: usize = 15;
+
+/// Validates a network interface name.
+fn parse_interface(s: &str) -> Result<String, String> {
+    let name = s.trim();
+    if name.is_empty() || name.len() > MAX_INTERFACE_LEN || name.contains(['/', ' ']) {
+        return Err(format!("invalid interface name \"{}\"", s));
+    }
+    Ok(name.to_string())
+}
+
+/// IP address family restriction for hostname resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    /// Returns the family of an address.
+    pub fn of(addr: &IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => IpFamily::V4,
+            IpAddr::V6(_) => IpFamily::V6,
+        }
+    }
+
+    /// Returns a short label ("v4" / "v6").
+    pub fn label(self) -> &'static str {
+        match self {
+            IpFamily::V4 => "v4",
+            IpFamily::V6 => "v6",
+        }
+    }
+}
+
+/// Address resolution state of a target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveState {
+    /// Address is known and the target is being pinged.
+    Resolved(IpAddr),
+    /// First resolution attempt is in progress.
+    Resolving,
+    /// Resolution failed; retrying in the background.
+    Unresolved(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct Target {
     pub name: String,
-    pub addr: IpAddr,
+    /// Host as given by the user (IP literal or hostname).
+    pub host: String,
+    /// How the target is probed.
+    pub probe: ProbeKind,
+    /// Payload size, timeout, TTL and DSCP used by the prober.
+    pub options: ProbeOptions,
+    /// Current resolution state.
+    pub state: ResolveState,
+    /// Per-target ping interval, overriding the global one.
+    pub interval: Option<Duration>,
+    /// Group this target belongs to, if any.
+    pub group: Option<String>,
+    /// Restrict hostname resolution to one address family.
+    pub family: Option<IpFamily>,
+    /// Expand the hostname into one row per resolved address.
+    pub all_addrs: bool,
+    /// Name of the hostname target this row was expanded from.
+    /// Expanded rows keep their address and are not re-resolved.
+    pub parent: Option<String>,
+    /// Whether the target is monitored (false once removed at runtime).
+    pub active: bool,
 }
 
 impl Target {
+    /// Creates a target with an already known address.
     pub fn new(name: impl Into<String>, addr: IpAddr) -> Self {
+        Self::from_host(name, addr.to_string())
+    }
+
+    /// Creates an ICMP target from a host string, deferring hostname resolution.
+    pub fn from_host(name: impl Into<String>, host: impl Into<String>) -> Self {
+        Self::from_spec(
+            name,
+            TargetSpec {
+                raw: String::new(),
+                host: host.into(),
+                probe: ProbeKind::Icmp,
+            },
+        )
+    }
+
+    /// Creates a target from a parsed target specification.
+    pub fn from_spec(name: impl Into<String>, spec: TargetSpec) -> Self {
+        let TargetSpec { host, probe, .. } = spec;
+        let state = match host.parse::<IpAddr>() {
+            Ok(addr) => ResolveState::Resolved(addr),
+            Err(_) => ResolveState::Resolving,
+        };
         Self {
             name: name.into(),
-            addr,
+            host,
+            probe,
+            options: ProbeOptions::default(),
+            state,
+            interval: None,
+            group: None,
+            family: None,
+            all_addrs: false,
+            parent: None,
+            active: true,
         }
     }
+
+    /// Returns the name for display, tagged with the address family for expanded rows.
+    pub fn display_name(&self) -> String {
+        match (&self.parent, self.addr()) {
+            (Some(_), Some(addr)) => format!("{} {}", self.name, IpFamily::of(&addr).label()),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// Returns whether the name, host or address contains `query`
+    /// (case-insensitive).
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [self.display_name(), self.host.clone(), self.addr_label()]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+
+    /// Returns the resolved address, if any.
+    pub fn addr(&self) -> Option<IpAddr> {
+        match self.state {
+            ResolveState::Resolved(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Returns the address for display (with the source address / interface
+    /// it is probed from), or the resolution state if unresolved.
+    pub fn addr_label(&self) -> String {
+        match &self.state {
+            ResolveState::Resolved(addr) => {
+                let mut label = match self.probe.port() {
+                    Some(port) => SocketAddr::new(*addr, port).to_string(),
+                    None => addr.to_string(),
+                };
+                if let Some(source) = self.options.source {
+                    label.push_str(&format!(" from {}", source));
+                }
+                if let Some(interface) = &self.options.interface {
+                    label.push_str(&format!(" via {}", interface));
+                }
+                label
+            }
+            ResolveState::Resolving => "resolving…".to_string(),
+            ResolveState::Unresolved(_) => "unresolved, retrying".to_string(),
+        }
+    }
+
+    /// Returns the labels that tell rows apart in exported metrics: name,
+    /// address (empty if unresolved) and probe, plus the port, interface and
+    /// source address where set.
+    pub fn series_labels(&self) -> Vec<(&'static str, String)> {
+        let mut labels = vec![
+            ("target", self.name.clone()),
+            (
+                "address",
+                self.addr().map(|a| a.to_string()).unwrap_or_default(),
+            ),
+            ("probe", self.probe.scheme().to_string()),
+        ];
+        if let Some(port) = self.probe.port() {
+            labels.push(("port", port.to_string()));
+        }
+        if let Some(interface) = &self.options.interface {
+            labels.push(("interface", interface.clone()));
+        }
+        if let Some(source) = self.options.source {
+            labels.push(("source", source.to_string()));
+        }
+        labels
+    }
+}
+
+/// Settings loaded from a TOML config file.
+#[derive(Debug, Default)]
+pub struct FileConfig {
+    /// Global ping interval in milliseconds.
+    pub interval: Option<u64>,
+    /// Whether to include the default targets.
+    pub default_targets: Option<bool>,
+    /// Whether to auto-detect the local gateway.
+    pub gateway: Option<bool>,
+    /// Whether to ping every resolved address of hostnames.
+    pub all_addrs: Option<bool>,
+    /// Address family restriction for hostnames.
+    pub family: Option<IpFamily>,
+    /// Global probe options.
+    pub probe: ProbeOverrides,
+    /// Latency histogram buckets.
+    pub latency_buckets: Option<BucketBounds>,
+    /// Targets declared in the file, in order.
+    pub targets: Vec<TargetEntry>,
+}
+
+/// A target declared in the config file.
+#[derive(Debug, Clone)]
+pub struct TargetEntry {
+    pub spec: TargetSpec,
+    pub name: Option<String>,
+    pub interval: Option<u64>,
+    pub group: Option<String>,
+    pub family: Option<IpFamily>,
+    pub all_addrs: Option<bool>,
+    /// Expected HTTP status codes (http(s) targets only).
+    pub expect_status: Option<StatusSet>,
+    /// HTTP request method (http(s) targets only).
+    pub method: Option<HttpMethod>,
+    /// Probe options overriding the global ones.
+    pub probe: ProbeOverrides,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    #[serde(default)]
+    defaults: RawDefaults,
+    #[serde(default, rename = "target")]
+    targets: Vec<RawTarget>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDefaults {
+    interval: Option<Spanned<u64>>,
+    default_targets: Option<bool>,
+    gateway: Option<bool>,
+    all_addrs: Option<bool>,
+    family: Option<IpFamily>,
+    payload_size: Option<Spanned<usize>>,
+    timeout: Option<Spanned<u64>>,
+    ttl: Option<Spanned<u32>>,
+    dscp: Option<Spanned<String>>,
+    pmtu: Option<bool>,
+    source: Option<Spanned<String>>,
+    interface: Option<Spanned<String>>,
+    latency_buckets: Option<Spanned<Vec<f64>>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTarget {
+    host: Spanned<String>,
+    name: Option<Spanned<String>>,
+    interval: Option<Spanned<u64>>,
+    group: Option<Spanned<String>>,
+    family: Option<IpFamily>,
+    all_addrs: Option<bool>,
+    expect_status: Option<Spanned<String>>,
+    method: Option<Spanned<String>>,
+    payload_size: Option<Spanned<usize>>,
+    timeout: Option<Spanned<u64>>,
+    ttl: Option<Spanned<u32>>,
+    dscp: Option<Spanned<String>>,
+    pmtu: Option<Spanned<bool>>,
+    source: Option<Spanned<String>>,
+    interface: Option<Spanned<String>>,
+}
+
+/// Returns the default config file location (~/.ptop/config.toml).
+fn default_config_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join(".ptop").join("config.toml")
+}
+
+/// Returns the 1-based line number of a byte offset in `src`.
+fn line_of(src: &str, offset: usize) -> usize {
+    src[..offset.min(src.len())].matches('\n').count() + 1
+}
+
+/// Validates probe option keys, reporting errors at their location.
+fn parse_probe_overrides(
+    payload_size: &Option<Spanned<usize>>,
+    timeout: &Option<Spanned<u64>>,
+    ttl: &Option<Spanned<u32>>,
+    dscp: &Option<Spanned<String>>,
+    source: &Option<Spanned<String>>,
+    interface: &Option<Spanned<String>>,
+    error_at: &mut impl FnMut(usize, String),
+) -> ProbeOverrides {
+    if let Some(size) = payload_size
+        && *size.get_ref() > MAX_PAYLOAD_SIZE
+    {
+        error_at(
+            size.span().start,
+            format!("payload_size must be at most {}", MAX_PAYLOAD_SIZE),
+        );
+    }
+    if let Some(timeout) = timeout
+        && *timeout.get_ref() == 0
+    {
+        error_at(
+            timeout.span().start,
+            "timeout must be greater than 0".into(),
+        );
+    }
+    if let Some(ttl) = ttl
+        && !(1..=255).contains(ttl.get_ref())
+    {
+        error_at(ttl.span().start, "ttl must be between 1 and 255".into());
+    }
+    let dscp = dscp.as_ref().and_then(|d| {
+        Dscp::parse(d.get_ref())
+            .map_err(|e| error_at(d.span().start, e))
+            .ok()
+    });
+    let source = source.as_ref().and_then(|s| {
+        s.get_ref()
+            .trim()
+            .parse::<IpAddr>()
+            .map_err(|_| {
+                error_at(
+                    s.span().start,
+                    format!("invalid source address \"{}\"", s.get_ref()),
+                )
+            })
+            .ok()
+    });
+    let interface = interface.as_ref().and_then(|i| {
+        parse_interface(i.get_ref())
+            .map_err(|e| error_at(i.span().start, e))
+            .ok()
+    });
+
+    ProbeOverrides {
+        payload_size: payload_size.as_ref().map(|s| *s.get_ref()),
+        timeout_ms: timeout.as_ref().map(|t| *t.get_ref()),
+        ttl: ttl.as_ref().map(|t| *t.get_ref()),
+        dscp,
+        pmtu: None,
+        source,
+        interface,
+    }
+}
+
+/// Loads the config file given by `--config`, or ~/.ptop/config.toml if it exists.
+pub fn load_config(args: &Args) -> Result<Option<FileConfig>> {
+    let path = match &args.config {
+        Some(p) => p.clone(),
+        None => {
+            let p = default_config_path();
+            if !p.exists() {
+                return Ok(None);
+            }
+            p
+        }
+    };
+
+    let src = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    parse_config(&src, &path).map(Some)
+}
+
+/// Parses and validates config file contents.
+pub fn parse_config(src: &str, path: &Path) -> Result<FileConfig> {
+    let raw: RawConfig = toml::from_str(src).map_err(|e| match e.span() {
+        Some(span) => anyhow::anyhow!(
+            "{}:{}: {}",
+            path.display(),
+            line_of(src, span.start),
+            e.message()
+        ),
+        None => anyhow::anyhow!("{}: {}", path.display(), e.message()),
+    })?;
+
+    let mut errors = Vec::new();
+    let mut error_at = |offset: usize, msg: String| {
+        errors.push(format!(
+            "{}:{}: {}",
+            path.display(),
+            line_of(src, offset),
+            msg
+        ));
+    };
+
+    if let Some(interval) = &raw.defaults.interval
+        && *interval.get_ref() == 0
+    {
+        error_at(
+            interval.span().start,
+            "interval must be greater than 0".into(),
+        );
+    }
+
+    let d = &raw.defaults;
+    let mut default_probe = parse_probe_overrides(
+        &d.payload_size,
+        &d.timeout,
+        &d.ttl,
+        &d.dscp,
+        &d.source,
+        &d.interface,
+        &mut error_at,
+    );
+    default_probe.pmtu = d.pmtu;
+    let latency_buckets = d.latency_buckets.as_ref().and_then(|b| {
+        BucketBounds::new(b.get_ref().clone())
+            .map_err(|e| error_at(b.span().start, format!("latency_buckets: {}", e)))
+            .ok()
+    });
+
+    let mut targets = Vec::with_capacity(raw.targets.len());
+    for t in &raw.targets {
+        let host = t.host.get_ref().trim();
+        let spec = if host.is_empty() {
+            error_at(t.host.span().start, "host must not be empty".into());
+            None
+        } else {
+            TargetSpec::parse(host)
+                .map_err(|e| error_at(t.host.span().start, e))
+                .ok()
+        };
+        if let Some(name) = &t.name
+            && name.get_ref().trim().is_empty()
+        {
+            error_at(name.span().start, "name must not be empty".into());
+        }
+        if let Some(interval) = &t.interval
+            && *interval.get_ref() == 0
+        {
+            error_at(
+                interval.span().start,
+                format!("target \"{}\": interval must be greater than 0", host),
+            );
+        }
+        if let Some(group) = &t.group
+            && group.get_ref().trim().is_empty()
+        {
+            error_at(group.span().start, "group must not be empty".into());
+        }
+
+        let is_http = spec
+            .as_ref()
+            .is_some_and(|s| matches!(s.probe, ProbeKind::Http(_)));
+        let mut http_option = |value: &Spanned<String>, key: &str| {
+            if !is_http {
+                error_at(
+                    value.span().start,
+                    format!("{} only applies to http(s) targets", key),
+                );
+            }
+        };
+        if let Some(v) = &t.expect_status {
+            http_option(v, "expect_status");
+        }
+        if let Some(v) = &t.method {
+            http_option(v, "method");
+        }
+        let expect_status = t.expect_status.as_ref().and_then(|v| {
+            StatusSet::parse(v.get_ref())
+                .map_err(|e| error_at(v.span().start, e))
+                .ok()
+        });
+        let method = t.method.as_ref().and_then(|v| {
+            HttpMethod::parse(v.get_ref())
+                .map_err(|e| error_at(v.span().start, e))
+                .ok()
+        });
+
+        if let Some(pmtu) = &t.pmtu
+            && spec.as_ref().is_some_and(|s| s.probe != ProbeKind::Icmp)
+        {
+            error_at(
+                pmtu.span().start,
+                "pmtu only applies to icmp targets".into(),
+            );
+        }
+        let mut probe = parse_probe_overrides(
+            &t.payload_size,
+            &t.timeout,
+            &t.ttl,
+            &t.dscp,
+            &t.source,
+            &t.interface,
+            &mut error_at,
+        );
+        probe.pmtu = t.pmtu.as_ref().map(|p| *p.get_ref());
+
+        let Some(spec) = spec else {
+            continue;
+        };
+        targets.push(TargetEntry {
+            spec,
+            name: t.name.as_ref().map(|n| n.get_ref().trim().to_string()),
+            interval: t.interval.as_ref().map(|i| *i.get_ref()),
+            group: t.group.as_ref().map(|g| g.get_ref().trim().to_string()),
+            family: t.family,
+            all_addrs: t.all_addrs,
+            expect_status,
+            method,
+            probe,
+        });
+    }
+
+    if !errors.is_empty() {
+        anyhow::bail!("Invalid config file:\n  {}", errors.join("\n  "));
+    }
+
+    Ok(FileConfig {
+        interval: raw.defaults.interval.map(|i| i.into_inner()),
+        default_targets: raw.defaults.default_targets,
+        gateway: raw.defaults.gateway,
+        all_addrs: raw.defaults.all_addrs,
+        family: raw.defaults.family,
+        probe: default_probe,
+        latency_buckets,
+        targets,
+    })
+}
+
+/// Returns the global ping interval: CLI flag, then config file, then the default.
+pub fn effective_interval(args: &Args, file: Option<&FileConfig>) -> Duration {
+    let ms = args
+        .interval
+        .or(file.and_then(|f| f.interval))
+        .unwrap_or(DEFAULT_INTERVAL_MS);
+    Duration::from_millis(ms)
+}
+
+/// Returns the latency histogram buckets: CLI flag, then config file, then
+/// the defaults.
+pub fn effective_buckets(args: &Args, file: Option<&FileConfig>) -> BucketBounds {
+    args.latency_buckets
+        .clone()
+        .or_else(|| file.and_then(|f| f.latency_buckets.clone()))
+        .unwrap_or_default()
 }
 
 /// Returns the default ping targets.
@@ -124,35 +781,381 @@ fn detect_gateway_linux() -> Option<Target> {
     None
 }
 
-/// Builds the complete target list based on CLI args.
-pub fn build_target_list(args: &Args) -> Vec<Target> {
+/// Builds the complete target list from CLI args and the optional config file.
+///
+/// Hostnames are not resolved here; their pingers resolve them in the background.
+pub fn build_target_list(args: &Args, file: Option<&FileConfig>) -> Vec<Target> {
     let mut targets = Vec::new();
 
     // Add gateway first if not disabled
-    if !args.no_gateway
-        && let Some(gw) = detect_gateway()
-    {
+    let gateway = !args.no_gateway && file.and_then(|f| f.gateway).unwrap_or(true);
+    if gateway && let Some(gw) = detect_gateway() {
         targets.push(gw);
     }
 
-    // Add default targets
-    if args.defaults {
+    // Add default targets; the config file only decides when neither flag is given
+    let with_defaults = !args.no_defaults
+        && (args.defaults || file.and_then(|f| f.default_targets).unwrap_or(true));
+    if with_defaults {
         targets.extend(default_targets());
     }
 
+    let defaults = TargetDefaults::new(args, file);
+    for target in &mut targets {
+        target.options = defaults.options.clone();
+    }
+
+    // Add targets declared in the config file
+    if let Some(file) = file {
+        for entry in &file.targets {
+            let name = entry.name.clone().unwrap_or_else(|| entry.spec.raw.clone());
+            let mut target = Target::from_spec(name, entry.spec.clone());
+            target.interval = entry.interval.map(Duration::from_millis);
+            target.group = entry.group.clone();
+            target.family = entry.family.or(defaults.family);
+            target.all_addrs = entry.all_addrs.unwrap_or(defaults.all_addrs);
+            target.options = defaults.options.clone();
+            entry.probe.apply(&mut target.options);
+            if let ProbeKind::Http(http) = &mut target.probe {
+                if let Some(expect) = entry.expect_status.clone().or(args.http_expect.clone()) {
+                    http.expect = expect;
+                }
+                if let Some(method) = entry.method {
+                    http.method = method;
+                }
+            }
+            targets.push(target);
+        }
+    }
+
     // Add user-specified targets
     for t in &args.targets {
-        if let Ok(addr) = t.parse::<IpAddr>() {
-            targets.push(Target::new(t.clone(), addr));
+        targets.push(defaults.target(t.clone()));
+    }
+
+    targets
+}
+
+/// Settings from the command line and config file applied to targets given
+/// without their own, such as command-line targets and targets added at
+/// runtime.
+#[derive(Debug, Clone, Default)]
+pub struct TargetDefaults {
+    /// Global probe options: defaults, then config file, then CLI flags.
+    pub options: ProbeOptions,
+    /// Address family restriction: CLI flags, then config file.
+    pub family: Option<IpFamily>,
+    /// Whether hostnames expand into one row per address.
+    pub all_addrs: bool,
+    /// Status codes counted as success for HTTP(S) targets.
+    pub http_expect: Option<StatusSet>,
+}
+
+impl TargetDefaults {
+    /// Collects the defaults from CLI args and the optional config file.
+    pub fn new(args: &Args, file: Option<&FileConfig>) -> Self {
+        let mut options = ProbeOptions::default();
+        if let Some(file) = file {
+            file.probe.apply(&mut options);
+        }
+        args.probe_overrides().apply(&mut options);
+
+        let family = if args.ipv4 {
+            Some(IpFamily::V4)
+        } else if args.ipv6 {
+            Some(IpFamily::V6)
         } else {
-            // Try to resolve hostname
-            if let Ok(addrs) = std::net::ToSocketAddrs::to_socket_addrs(&(t.as_str(), 0))
-                && let Some(sock_addr) = addrs.into_iter().next()
-            {
-                targets.push(Target::new(t.clone(), sock_addr.ip()));
-            }
+            file.and_then(|f| f.family)
+        };
+
+        Self {
+            options,
+            family,
+            all_addrs: args.all_addrs || file.and_then(|f| f.all_addrs).unwrap_or(false),
+            http_expect: args.http_expect.clone(),
         }
     }
 
-    targets
+    /// Creates a target named after its specification.
+    pub fn target(&self, spec: TargetSpec) -> Target {
+        let mut target = Target::from_spec(spec.raw.clone(), spec);
+        target.family = self.family;
+        target.all_addrs = self.all_addrs;
+        target.options = self.options.clone();
+        if let ProbeKind::Http(http) = &mut target.probe
+            && let Some(expect) = &self.http_expect
+        {
+            http.expect = expect.clone();
+        }
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> Result<FileConfig> {
+        parse_config(src, Path::new("config.toml"))
+    }
+
+    #[test]
+    fn test_target_matches() {
+        let mut target = Target::from_host("Cloudflare", "one.one.one.one");
+        target.state = ResolveState::Resolved("1.1.1.1".parse().unwrap());
+
+        assert!(target.matches("cloud"));
+        assert!(target.matches("one.one"));
+        assert!(target.matches("1.1.1"));
+        assert!(!target.matches("google"));
+    }
+
+    #[test]
+    fn test_parse_config_targets() {
+        let cfg = parse(
+            r#"
+[defaults]
+interval = 500
+gateway = false
+
+[[target]]
+host = "10.0.0.1"
+name = "Router"
+group = "LAN"
+
+[[target]]
+host = "192.0.2.7"
+interval = 250
+family = "v6"
+all_addrs = true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(cfg.interval, Some(500));
+        assert_eq!(cfg.gateway, Some(false));
+        assert_eq!(cfg.default_targets, None);
+        assert_eq!(cfg.targets.len(), 2);
+        assert_eq!(cfg.targets[0].name.as_deref(), Some("Router"));
+        assert_eq!(cfg.targets[0].group.as_deref(), Some("LAN"));
+        assert_eq!(cfg.targets[1].interval, Some(250));
+        assert_eq!(cfg.targets[1].family, Some(IpFamily::V6));
+        assert_eq!(cfg.targets[1].all_addrs, Some(true));
+    }
+
+    #[test]
+    fn test_parse_config_reports_line_numbers() {
+        let err = parse(
+            r#"[[target]]
+host = "10.0.0.1"
+
+[[target]]
+host = ""
+interval = 0
+"#,
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(
+            err.contains("config.toml:5: host must not be empty"),
+            "{}",
+            err
+        );
+        assert!(err.contains("config.toml:6:"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_config_invalid_probe() {
+        let err = parse("[[target]]\nhost = \"tcp://example.com\"\n")
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("config.toml:2: missing port"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_config_latency_buckets() {
+        let config = parse("[defaults]\nlatency_buckets = [1, 2.5, 10]\n").unwrap();
+        assert_eq!(
+            config.latency_buckets.unwrap().as_ms(),
+            [1.0, 2.5, 10.0].as_slice()
+        );
+
+        let err = parse("[defaults]\nlatency_buckets = [10, 5]\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("config.toml:2: latency_buckets:"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_config_http_options() {
+        let cfg = parse(
+            "[[target]]\nhost = \"https://example.com/\"\nexpect_status = \"200,301\"\nmethod = \"get\"\n",
+        )
+        .unwrap();
+        assert_eq!(cfg.targets[0].method, Some(HttpMethod::Get));
+        assert!(cfg.targets[0].expect_status.as_ref().unwrap().contains(301));
+
+        let err = parse("[[target]]\nhost = \"10.0.0.1\"\nexpect_status = \"200\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("config.toml:3: expect_status only applies to http(s) targets"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_parse_config_unknown_key() {
+        let err = parse("[[target]]\nhost = \"10.0.0.1\"\nnmae = \"typo\"\n")
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.starts_with("config.toml:3:"), "{}", err);
+        assert!(err.contains("nmae"), "{}", err);
+    }
+
+    #[test]
+    fn test_build_target_list_merges_config() {
+        let args = Args::parse_from(["ptop", "--no-gateway", "-t", "192.0.2.1"]);
+        let cfg = parse(
+            r#"
+[defaults]
+default_targets = false
+
+[[target]]
+host = "10.0.0.1"
+name = "Router"
+interval = 250
+"#,
+        )
+        .unwrap();
+
+        let targets = build_target_list(&args, Some(&cfg));
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].name, "Router");
+        assert_eq!(targets[0].interval, Some(Duration::from_millis(250)));
+        assert_eq!(targets[1].name, "192.0.2.1");
+    }
+
+    #[test]
+    fn test_build_target_list_default_targets_precedence() {
+        let cfg = parse("[defaults]\ndefault_targets = false\n").unwrap();
+        let count = |argv: &[&str], cfg: Option<&FileConfig>| {
+            let args = Args::parse_from([&["ptop", "--no-gateway"], argv].concat());
+            build_target_list(&args, cfg).len()
+        };
+
+        assert_eq!(count(&[], None), 3);
+        assert_eq!(count(&[], Some(&cfg)), 0);
+        // Flags override the config file
+        assert_eq!(count(&["-d"], Some(&cfg)), 3);
+        assert_eq!(count(&["--no-defaults"], None), 0);
+    }
+
+    #[test]
+    fn test_build_target_list_probe_options() {
+        let args = Args::parse_from(["ptop", "--no-gateway", "-t", "192.0.2.1", "--ttl", "32"]);
+        let cfg = parse(
+            r#"
+[defaults]
+default_targets = false
+timeout = 2000
+ttl = 8
+
+[[target]]
+host = "10.0.0.1"
+payload_size = 1200
+dscp = "EF"
+"#,
+        )
+        .unwrap();
+
+        let targets = build_target_list(&args, Some(&cfg));
+        let router = &targets[0].options;
+        assert_eq!(router.payload_size, 1200);
+        assert_eq!(router.timeout_ms, 2000);
+        assert_eq!(router.ttl, Some(32));
+        assert_eq!(router.dscp, Some(Dscp(46)));
+        let cli = &targets[1].options;
+        assert_eq!(cli.payload_size, 56);
+        assert_eq!(cli.timeout_ms, 2000);
+        assert_eq!(cli.dscp, None);
+
+        let err = parse("[[target]]\nhost = \"tcp://10.0.0.1:22\"\npmtu = true\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("config.toml:3: pmtu only applies to icmp targets"),
+            "{}",
+            err
+        );
+
+        let err = parse("[defaults]\nttl = 0\n\n[[target]]\nhost = \"10.0.0.1\"\ndscp = \"XX\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("config.toml:2: ttl must be between 1 and 255"),
+            "{}",
+            err
+        );
+        assert!(err.contains("config.toml:6:"), "{}", err);
+    }
+
+    #[test]
+    fn test_build_target_list_binding() {
+        let args = Args::parse_from(["ptop", "--no-gateway", "--no-defaults"]);
+        let cfg = parse(
+            r#"
+[[target]]
+host = "1.1.1.1"
+name = "Fiber"
+interface = "eth0"
+
+[[target]]
+host = "1.1.1.1"
+name = "LTE"
+interface = "wwan0"
+source = "100.64.0.2"
+"#,
+        )
+        .unwrap();
+
+        let targets = build_target_list(&args, Some(&cfg));
+        assert_eq!(targets[0].addr_label(), "1.1.1.1 via eth0");
+        assert_eq!(targets[1].addr_label(), "1.1.1.1 from 100.64.0.2 via wwan0");
+        assert_eq!(
+            targets[1].options.source,
+            Some("100.64.0.2".parse().unwrap())
+        );
+
+        let err = parse(
+            "[[target]]\nhost = \"1.1.1.1\"\nsource = \"lte\"\ninterface = \"a-very-long-ifname\"\n",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains("config.toml:3: invalid source address"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("config.toml:4: invalid interface name"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_target_from_host_state() {
+        let ip = Target::from_host("ip", "192.0.2.1");
+        assert_eq!(ip.addr(), Some("192.0.2.1".parse().unwrap()));
+
+        let host = Target::from_host("host", "example.invalid");
+        assert_eq!(host.state, ResolveState::Resolving);
+        assert_eq!(host.addr(), None);
+        assert_eq!(host.addr_label(), "resolving…");
+    }
 }
@@ -0,0 +1,145 @@
+use crate::app::App;
+use crate::config::ResolveState;
+use crate::logging::{SessionSummary, TargetSummary};
+use crate::stats::format_elapsed;
+use anyhow::Result;
+use std::fmt::Write;
+use std::time::Duration;
+use tokio::time::{Instant, MissedTickBehavior, interval};
+
+/// How often pinger updates are processed without a UI.
+const TICK_RATE: Duration = Duration::from_millis(100);
+
+/// Runs the app without a terminal UI until `duration` has passed, every
+/// target has sent `count` probes, SIGINT/SIGTERM arrives, or the reader of
+/// the output stream goes away.
+pub async fn run(app: &mut App, duration: Option<Duration>, count: Option<u64>) -> Result<()> {
+    let deadline = duration.map(|d| Instant::now() + d);
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    let mut tick = interval(TICK_RATE);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = tick.tick() => {}
+        }
+        app.process_updates();
+
+        if deadline.is_some_and(|d| Instant::now() >= d)
+            || count.is_some_and(|n| count_reached(app, n))
+            || app.logger.stream.as_ref().is_some_and(|s| s.is_closed())
+        {
+            break;
+        }
+    }
+
+    // Results that arrived since the last tick
+    app.process_updates();
+    Ok(())
+}
+
+/// Returns whether every target has sent `count` probes. Targets whose
+/// hostname doesn't resolve don't hold up the exit.
+fn count_reached(app: &App, count: u64) -> bool {
+    app.targets
+        .iter()
+        .zip(&app.stats)
+        .filter(|(target, _)| target.active)
+        .all(|(target, stats)| {
+            stats.sent >= count || matches!(target.state, ResolveState::Unresolved(_))
+        })
+}
+
+/// Completes on Ctrl-C (SIGINT) or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Formats the session summary as a plain-text table.
+pub fn format_summary(summary: &SessionSummary) -> String {
+    let mut out = String::new();
+    let elapsed = Duration::from_secs(summary.duration_secs);
+    let _ = writeln!(
+        out,
+        "--- ptop summary: {} targets, {} ---",
+        summary.targets.len(),
+        format_elapsed(elapsed)
+    );
+    let _ = writeln!(
+        out,
+        "{:<24} {:<20} {:>7} {:>7} {:>7} {:>8} {:>8} {:>8} {:>8} {:>8} {:>5}",
+        "Target", "Address", "Sent", "Recv", "Loss", "Min", "Avg", "P95", "Max", "Jitter", "Grade"
+    );
+    for target in &summary.targets {
+        let _ = writeln!(out, "{}", format_target(target));
+    }
+    out
+}
+
+/// Formats one target line of the summary (latencies in milliseconds).
+fn format_target(t: &TargetSummary) -> String {
+    let ms = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.1}", v));
+    let addr = if t.addr.is_empty() { "-" } else { &t.addr };
+    format!(
+        "{:<24} {:<20} {:>7} {:>7} {:>6.1}% {:>8} {:>8} {:>8} {:>8} {:>8} {:>5}",
+        t.name,
+        addr,
+        t.sent,
+        t.received,
+        t.loss_pct,
+        ms(t.latency_ms.min),
+        ms(t.latency_ms.avg),
+        ms(t.latency_ms.p95),
+        ms(t.latency_ms.max),
+        ms(t.jitter_ms),
+        t.quality_grade.as_deref().unwrap_or("-"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Target;
+    use crate::stats::{PingResult, TargetStats};
+    use chrono::Utc;
+
+    #[test]
+    fn test_format_summary() {
+        let targets = vec![
+            Target::new("Cloudflare", "1.1.1.1".parse().unwrap()),
+            Target::from_host("nowhere", "nowhere.invalid"),
+        ];
+        let mut stats = vec![TargetStats::new(), TargetStats::new()];
+        stats[0].record(PingResult::Success(Duration::from_millis(12)));
+        stats[0].record(PingResult::Timeout);
+
+        let started = Utc::now();
+        let ended = started + chrono::Duration::seconds(65);
+        let text = format_summary(&SessionSummary::new(started, ended, &targets, &stats));
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[0], "--- ptop summary: 2 targets, 1m 5s ---");
+        assert!(lines[1].starts_with("Target"));
+        let cloudflare: Vec<&str> = lines[2].split_whitespace().collect();
+        assert_eq!(
+            cloudflare[..6],
+            ["Cloudflare", "1.1.1.1", "2", "1", "50.0%", "12.0"]
+        );
+        let nowhere: Vec<&str> = lines[3].split_whitespace().collect();
+        assert_eq!(nowhere[..5], ["nowhere", "-", "0", "0", "0.0%"]);
+    }
+}
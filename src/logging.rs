@@ -1,14 +1,16 @@
 use crate::config::Target;
-use crate::stats::TargetStats;
-use anyhow::Result;
+use crate::output::EventStream;
+use crate::probe::ProbeOptions;
+use crate::stats::{FailureKind, LatencyHistogram, PingResult, TargetStats};
+use anyhow::{Result, anyhow};
 use chrono::{DateTime, Utc};
 use flate2::Compression;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::IpAddr;
 use std::path::PathBuf;
-use std::time::Duration;
 
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
@@ -21,6 +23,34 @@ fn data_dir() -> Result<PathBuf> {
     Ok(dir)
 }
 
+/// Kind of a logged event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Result of a single ping (also assumed for logs without a `kind` field).
+    #[default]
+    Ping,
+    /// The target's hostname resolved to a new address (`target_addr`).
+    AddressChanged,
+    /// Probe settings of a target, written once at session start.
+    TargetInfo,
+    /// A target was added at runtime (with its probe settings; the address
+    /// may still be empty while resolving).
+    TargetAdded,
+    /// A target was removed at runtime.
+    TargetRemoved,
+    /// Probing of a target was paused.
+    TargetPaused,
+    /// Probing of a target was resumed.
+    TargetResumed,
+}
+
+impl EventKind {
+    fn is_ping(&self) -> bool {
+        *self == EventKind::Ping
+    }
+}
+
 /// A single ping event for logging/replay.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PingEvent {
@@ -34,6 +64,18 @@ pub struct PingEvent {
     pub target_addr: String,
     /// Latency in microseconds, or None for timeout/error.
     pub latency_us: Option<u64>,
+    /// Why the ping failed (logs without it replay failures as timeouts).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+    /// Event kind.
+    #[serde(default, skip_serializing_if = "EventKind::is_ping")]
+    pub kind: EventKind,
+    /// Previous address, for address change events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_addr: Option<String>,
+    /// Probe settings, for target info events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<ProbeOptions>,
 }
 
 /// Session summary for JSON export.
@@ -45,6 +87,28 @@ pub struct SessionSummary {
     pub targets: Vec<TargetSummary>,
 }
 
+impl SessionSummary {
+    /// Summarizes the active targets of a session.
+    pub fn new(
+        started: DateTime<Utc>,
+        ended: DateTime<Utc>,
+        targets: &[Target],
+        stats: &[TargetStats],
+    ) -> Self {
+        Self {
+            started,
+            ended,
+            duration_secs: ended.signed_duration_since(started).num_seconds() as u64,
+            targets: targets
+                .iter()
+                .zip(stats.iter())
+                .filter(|(target, _)| target.active)
+                .map(|(target, stat)| TargetSummary::new(target, stat))
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct TargetSummary {
     pub name: String,
@@ -56,6 +120,40 @@ pub struct TargetSummary {
     pub jitter_ms: Option<f64>,
     pub mos: Option<f64>,
     pub quality_grade: Option<String>,
+    #[serde(default)]
+    pub options: ProbeOptions,
+    /// All-time latency histogram.
+    #[serde(default)]
+    pub latency_histogram: Option<LatencyHistogram>,
+    /// Latency histogram of the recent window.
+    #[serde(default)]
+    pub window_latency_histogram: Option<LatencyHistogram>,
+}
+
+impl TargetSummary {
+    /// Summarizes a target's all-time statistics.
+    pub fn new(target: &Target, stat: &TargetStats) -> Self {
+        Self {
+            name: target.name.clone(),
+            addr: target.addr().map(|a| a.to_string()).unwrap_or_default(),
+            sent: stat.sent,
+            received: stat.received,
+            loss_pct: stat.packet_loss(),
+            latency_ms: LatencySummary {
+                min: stat.all_time.min.map(|d| d.as_secs_f64() * 1000.0),
+                avg: stat.all_time.average().map(|d| d.as_secs_f64() * 1000.0),
+                p50: stat.all_time.p50().map(|d| d.as_secs_f64() * 1000.0),
+                p95: stat.all_time.p95().map(|d| d.as_secs_f64() * 1000.0),
+                max: stat.all_time.max.map(|d| d.as_secs_f64() * 1000.0),
+            },
+            jitter_ms: stat.jitter().map(|d| d.as_secs_f64() * 1000.0),
+            mos: stat.mos_score(),
+            quality_grade: stat.quality_grade().map(|(g, _)| g.to_string()),
+            options: target.options.clone(),
+            latency_histogram: Some(stat.histogram.clone()),
+            window_latency_histogram: Some(stat.window_histogram.clone()),
+        }
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -67,6 +165,46 @@ pub struct LatencySummary {
     pub max: Option<f64>,
 }
 
+/// A target's all-time latency histogram added up across sessions.
+#[derive(Debug, serde::Serialize)]
+pub struct MergedHistogram {
+    pub name: String,
+    pub addr: String,
+    /// Number of sessions the target appears in.
+    pub sessions: usize,
+    pub latency_histogram: LatencyHistogram,
+}
+
+/// Merges the all-time latency histograms of targets with the same name and
+/// address across sessions, in order of first appearance. Targets from
+/// summaries without histograms are skipped.
+pub fn merge_histograms(sessions: &[SessionSummary]) -> Result<Vec<MergedHistogram>> {
+    let mut merged: Vec<MergedHistogram> = Vec::new();
+    for target in sessions.iter().flat_map(|s| &s.targets) {
+        let Some(histogram) = &target.latency_histogram else {
+            continue;
+        };
+        match merged
+            .iter_mut()
+            .find(|m| m.name == target.name && m.addr == target.addr)
+        {
+            Some(m) => {
+                m.latency_histogram
+                    .merge(histogram)
+                    .map_err(|e| anyhow!("{} ({}): {}", target.name, target.addr, e))?;
+                m.sessions += 1;
+            }
+            None => merged.push(MergedHistogram {
+                name: target.name.clone(),
+                addr: target.addr.clone(),
+                sessions: 1,
+                latency_histogram: histogram.clone(),
+            }),
+        }
+    }
+    Ok(merged)
+}
+
 /// How often to flush logs (in number of events).
 const FLUSH_INTERVAL: u64 = 50;
 
@@ -87,6 +225,8 @@ pub struct SessionLogger {
     last_summary_at: DateTime<Utc>,
     /// Path for the running summary.
     summary_path: Option<PathBuf>,
+    /// Stdout stream of events and aggregates, if enabled.
+    pub stream: Option<EventStream>,
 }
 
 impl SessionLogger {
@@ -130,25 +270,128 @@ impl SessionLogger {
             event_count: 0,
             last_summary_at: started,
             summary_path,
+            stream: None,
         })
     }
 
+    /// Also streams every event to `stream`.
+    pub fn with_stream(mut self, stream: EventStream) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
     /// Logs a ping event.
     pub fn log_ping(
         &mut self,
         target_idx: usize,
         target: &Target,
-        latency: Option<Duration>,
+        result: &PingResult,
+    ) -> Result<()> {
+        let latency = match result {
+            PingResult::Success(d) => Some(*d),
+            _ => None,
+        };
+        self.write_event(PingEvent {
+            timestamp: Utc::now(),
+            target_idx,
+            target_name: target.name.clone(),
+            target_addr: target.addr().map(|a| a.to_string()).unwrap_or_default(),
+            latency_us: latency.map(|d| d.as_micros() as u64),
+            failure: result.failure(),
+            kind: EventKind::Ping,
+            previous_addr: None,
+            options: None,
+        })
+    }
+
+    /// Logs a change of a target's resolved address.
+    pub fn log_address_change(
+        &mut self,
+        target_idx: usize,
+        target: &Target,
+        previous: IpAddr,
     ) -> Result<()> {
+        self.write_event(PingEvent {
+            timestamp: Utc::now(),
+            target_idx,
+            target_name: target.name.clone(),
+            target_addr: target.addr().map(|a| a.to_string()).unwrap_or_default(),
+            latency_us: None,
+            failure: None,
+            kind: EventKind::AddressChanged,
+            previous_addr: Some(previous.to_string()),
+            options: None,
+        })
+    }
+
+    /// Logs a target's probe settings.
+    pub fn log_target_info(&mut self, target_idx: usize, target: &Target) -> Result<()> {
+        let options = Some(target.options.clone());
+        self.log_target_event(target_idx, target, EventKind::TargetInfo, options)
+    }
+
+    /// Logs a target added at runtime.
+    pub fn log_target_added(&mut self, target_idx: usize, target: &Target) -> Result<()> {
+        let options = Some(target.options.clone());
+        self.log_target_event(target_idx, target, EventKind::TargetAdded, options)
+    }
+
+    /// Logs a target removed at runtime.
+    pub fn log_target_removed(&mut self, target_idx: usize, target: &Target) -> Result<()> {
+        self.log_target_event(target_idx, target, EventKind::TargetRemoved, None)
+    }
+
+    /// Logs a target being paused or resumed.
+    pub fn log_pause_change(
+        &mut self,
+        target_idx: usize,
+        target: &Target,
+        paused: bool,
+    ) -> Result<()> {
+        let kind = if paused {
+            EventKind::TargetPaused
+        } else {
+            EventKind::TargetResumed
+        };
+        self.log_target_event(target_idx, target, kind, None)
+    }
+
+    /// Writes an event about a target (rather than a probe result).
+    fn log_target_event(
+        &mut self,
+        target_idx: usize,
+        target: &Target,
+        kind: EventKind,
+        options: Option<ProbeOptions>,
+    ) -> Result<()> {
+        self.write_event(PingEvent {
+            timestamp: Utc::now(),
+            target_idx,
+            target_name: target.name.clone(),
+            target_addr: target.addr().map(|a| a.to_string()).unwrap_or_default(),
+            latency_us: None,
+            failure: None,
+            kind,
+            previous_addr: None,
+            options,
+        })
+    }
+
+    /// Writes an event to the raw log and the stream, if enabled. The raw log
+    /// is the record of the session, so it is written even if the stream fails.
+    fn write_event(&mut self, event: PingEvent) -> Result<()> {
+        let logged = self.write_raw_event(&event);
+        let streamed = match &mut self.stream {
+            Some(stream) => stream.write_event(&event),
+            None => Ok(()),
+        };
+        logged.and(streamed)
+    }
+
+    /// Appends an event to the raw log, if enabled.
+    fn write_raw_event(&mut self, event: &PingEvent) -> Result<()> {
         if let Some(writer) = &mut self.event_writer {
-            let event = PingEvent {
-                timestamp: Utc::now(),
-                target_idx,
-                target_name: target.name.clone(),
-                target_addr: target.addr.to_string(),
-                latency_us: latency.map(|d| d.as_micros() as u64),
-            };
-            let line = serde_json::to_string(&event)?;
+            let line = serde_json::to_string(event)?;
             writeln!(writer, "{}", line)?;
 
             self.event_count += 1;
@@ -209,36 +452,7 @@ impl SessionLogger {
             None => return Ok(()),
         };
 
-        let duration = ended.signed_duration_since(self.started);
-
-        let target_summaries: Vec<TargetSummary> = targets
-            .iter()
-            .zip(stats.iter())
-            .map(|(target, stat)| TargetSummary {
-                name: target.name.clone(),
-                addr: target.addr.to_string(),
-                sent: stat.sent,
-                received: stat.received,
-                loss_pct: stat.packet_loss(),
-                latency_ms: LatencySummary {
-                    min: stat.all_time.min.map(|d| d.as_secs_f64() * 1000.0),
-                    avg: stat.all_time.average().map(|d| d.as_secs_f64() * 1000.0),
-                    p50: stat.all_time.p50().map(|d| d.as_secs_f64() * 1000.0),
-                    p95: stat.all_time.p95().map(|d| d.as_secs_f64() * 1000.0),
-                    max: stat.all_time.max.map(|d| d.as_secs_f64() * 1000.0),
-                },
-                jitter_ms: stat.jitter().map(|d| d.as_secs_f64() * 1000.0),
-                mos: stat.mos_score(),
-                quality_grade: stat.quality_grade().map(|(g, _)| g.to_string()),
-            })
-            .collect();
-
-        let summary = SessionSummary {
-            started: self.started,
-            ended,
-            duration_secs: duration.num_seconds() as u64,
-            targets: target_summaries,
-        };
+        let summary = SessionSummary::new(self.started, ended, targets, stats);
 
         let mut opts = OpenOptions::new();
         opts.create(true).write(true).truncate(true);
@@ -297,7 +511,6 @@ pub fn load_events(path: &PathBuf) -> Result<Vec<PingEvent>> {
 }
 
 /// Loads a session summary from a gzipped JSON file.
-#[allow(dead_code)]
 pub fn load_session(path: &PathBuf) -> Result<SessionSummary> {
     let file = File::open(path)?;
     let decoder = GzDecoder::new(file);
@@ -341,3 +554,37 @@ pub fn list_logs() -> Result<Vec<PathBuf>> {
     logs.reverse(); // Most recent first
     Ok(logs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::BucketBounds;
+    use std::time::Duration;
+
+    fn session(latencies_ms: &[u64], buckets: &str) -> SessionSummary {
+        let targets = vec![Target::new("Cloudflare", "1.1.1.1".parse().unwrap())];
+        let mut stats = vec![TargetStats::with_buckets(
+            BucketBounds::parse(buckets).unwrap(),
+        )];
+        for &ms in latencies_ms {
+            stats[0].record(PingResult::Success(Duration::from_millis(ms)));
+        }
+        SessionSummary::new(Utc::now(), Utc::now(), &targets, &stats)
+    }
+
+    #[test]
+    fn test_merge_histograms() {
+        let sessions = [session(&[3, 20], "5,50"), session(&[80], "5,50")];
+        let merged = merge_histograms(&sessions).unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].sessions, 2);
+        assert_eq!(merged[0].latency_histogram.counts, vec![1, 2]);
+        assert_eq!(merged[0].latency_histogram.count, 3);
+        assert_eq!(merged[0].latency_histogram.sum_us, 103_000);
+
+        let err = merge_histograms(&[session(&[3], "5,50"), session(&[3], "10")])
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Cloudflare (1.1.1.1): "), "{}", err);
+    }
+}
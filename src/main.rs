@@ -1,15 +1,25 @@
 mod app;
 mod config;
+mod headless;
 mod logging;
+mod metrics;
+mod output;
 mod ping;
+mod pmtu;
+mod probe;
+mod push;
 mod replay;
+mod resolve;
 mod stats;
+mod trace;
 mod ui;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use app::App;
 use clap::Parser;
-use config::{Args, build_target_list};
+use config::{
+    Args, TargetDefaults, build_target_list, effective_buckets, effective_interval, load_config,
+};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
@@ -35,13 +45,13 @@ fn check_icmp_permissions() -> bool {
         #[cfg(target_os = "linux")]
         {
             if let Ok(content) = std::fs::read_to_string("/proc/sys/net/ipv4/ping_group_range") {
-                let parts: Vec<&str> = content.trim().split_whitespace().collect();
-                if parts.len() == 2 {
-                    if let (Ok(min), Ok(max)) = (parts[0].parse::<u32>(), parts[1].parse::<u32>()) {
-                        let gid = unsafe { libc::getegid() };
-                        if gid >= min && gid <= max {
-                            return true;
-                        }
+                let parts: Vec<&str> = content.split_whitespace().collect();
+                if parts.len() == 2
+                    && let (Ok(min), Ok(max)) = (parts[0].parse::<u32>(), parts[1].parse::<u32>())
+                {
+                    let gid = unsafe { libc::getegid() };
+                    if gid >= min && gid <= max {
+                        return true;
                     }
                 }
             }
@@ -93,6 +103,16 @@ async fn main() -> Result<()> {
         return list_available_sessions();
     }
 
+    // Handle --merge-sessions
+    if !args.merge_sessions.is_empty() {
+        return merge_sessions(&args.merge_sessions);
+    }
+
+    // Handle --udp-echo-server
+    if let Some(bind) = args.udp_echo_server {
+        return probe::run_echo_server(bind).await;
+    }
+
     // Handle --replay
     if let Some(replay_path) = &args.replay {
         return run_replay_mode(replay_path, args.speed).await;
@@ -145,7 +165,19 @@ fn list_available_sessions() -> Result<()> {
         }
     }
     println!("\nView with: zcat <path> | jq");
+    println!("Merge latency histograms with: ptop --merge-sessions <path>...");
+
+    Ok(())
+}
 
+/// Prints the latency histograms of the given session summaries, merged per target.
+fn merge_sessions(paths: &[PathBuf]) -> Result<()> {
+    let sessions = paths
+        .iter()
+        .map(|path| logging::load_session(path).with_context(|| path.display().to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    let merged = logging::merge_histograms(&sessions)?;
+    println!("{}", serde_json::to_string_pretty(&merged)?);
     Ok(())
 }
 
@@ -161,9 +193,9 @@ async fn run_replay_mode(path: &str, speed: f64) -> Result<()> {
     // Load replay state
     let mut replay = ReplayState::new(&path, speed)?;
     let events = logging::load_events(&path)?;
-    let (targets, mut stats) = replay::build_replay_targets(&events);
+    let (mut targets, mut stats) = replay::build_replay_targets(&events);
 
-    if targets.is_empty() {
+    if !targets.iter().any(|t| t.addr().is_some()) {
         eprintln!("No valid targets found in log file.");
         std::process::exit(1);
     }
@@ -182,7 +214,7 @@ async fn run_replay_mode(path: &str, speed: f64) -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     // Main replay loop
-    let res = run_replay_app(&mut terminal, &mut replay, &targets, &mut stats).await;
+    let res = run_replay_app(&mut terminal, &mut replay, &mut targets, &mut stats).await;
 
     // Restore terminal
     disable_raw_mode()?;
@@ -204,42 +236,78 @@ async fn run_replay_mode(path: &str, speed: f64) -> Result<()> {
 
 /// Runs the application in live mode.
 async fn run_live_mode(args: Args) -> Result<()> {
-    // Check permissions before starting
-    if !check_icmp_permissions() {
-        print_permission_help();
-        std::process::exit(1);
-    }
+    let file_config = match load_config(&args) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let targets = build_target_list(&args);
+    let targets = build_target_list(&args, file_config.as_ref());
+    let targets = resolve::expand_targets(targets).await;
 
     if targets.is_empty() {
         eprintln!("No targets specified. Use -t to add targets or -d to include defaults.");
         std::process::exit(1);
     }
 
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    // Check permissions before starting (TCP/UDP-only target lists need none)
+    if targets.iter().any(|t| t.probe.needs_raw_socket()) && !check_icmp_permissions() {
+        print_permission_help();
+        std::process::exit(1);
+    }
 
     // Create app
+    let mut logger = logging::SessionLogger::new(args.log_raw, args.summary)?;
+    if let Some(format) = args.output {
+        let every = Duration::from_secs(args.aggregate_interval);
+        logger = logger.with_stream(output::EventStream::stdout(format, every));
+    }
     let mut app = App::new(
         targets,
-        Duration::from_millis(args.interval),
-        args.log_raw,
-        args.summary,
+        TargetDefaults::new(&args, file_config.as_ref()),
+        effective_interval(&args, file_config.as_ref()),
+        (args.resolve_interval > 0).then(|| Duration::from_secs(args.resolve_interval)),
+        effective_buckets(&args, file_config.as_ref()),
+        logger,
     )?;
 
+    if let Some(addr) = args.metrics_listen {
+        let server = metrics::MetricsServer::bind(addr).await?;
+        eprintln!("Serving metrics on http://{}/metrics", server.addr);
+        app.metrics = Some(server);
+    }
+
+    if let Some(target) = &args.push {
+        let every = args.push_interval.map(Duration::from_secs);
+        let sink = push::PushSink::connect(target, every).await?;
+        eprintln!("Pushing metrics to {}", sink.addr);
+        app.push = Some(sink);
+    }
+
     if args.log_raw
         && let Some(path) = &app.logger.event_log_path
     {
         eprintln!("Logging to: {}", path.display());
     }
 
+    if args.headless {
+        return run_headless_mode(&args, &mut app).await;
+    }
+
+    // Setup terminal
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
     // Main loop
     let res = run_live_app(&mut terminal, &mut app).await;
+    if let Some(push) = &mut app.push {
+        push.finish().await;
+    }
 
     // Write session summary before restoring terminal (only if enabled)
     let summary_path = app.logger.write_summary(&app.targets, &app.stats)?;
@@ -261,6 +329,7 @@ async fn run_live_mode(args: Args) -> Result<()> {
     if let Some(log_path) = &app.logger.event_log_path {
         println!("Raw ping log saved to: {}", log_path.display());
     }
+    report_push_drops(&app);
 
     if let Err(e) = res {
         eprintln!("Error: {}", e);
@@ -270,6 +339,54 @@ async fn run_live_mode(args: Args) -> Result<()> {
     Ok(())
 }
 
+/// Runs live mode without the TUI, printing the session summary to stdout
+/// (log locations go to stderr). With `--output`, stdout carries the event
+/// stream and the summary is its last record.
+async fn run_headless_mode(args: &Args, app: &mut App) -> Result<()> {
+    eprintln!(
+        "Monitoring {} targets (Ctrl-C to stop)...",
+        app.targets.len()
+    );
+
+    let res = headless::run(app, args.duration.map(Duration::from_secs), args.count).await;
+    if let Some(push) = &mut app.push {
+        push.finish().await;
+    }
+
+    // Flush the log and summary even if the loop failed
+    let summary_path = app.logger.write_summary(&app.targets, &app.stats)?;
+    app.logger.finish()?;
+
+    let summary =
+        logging::SessionSummary::new(app.started_at, chrono::Utc::now(), &app.targets, &app.stats);
+    match &mut app.logger.stream {
+        Some(stream) => stream.write_summary(&summary)?,
+        None => print!("{}", headless::format_summary(&summary)),
+    }
+
+    if let Some(path) = &summary_path {
+        eprintln!("Session summary saved to: {}", path.display());
+    }
+    if let Some(log_path) = &app.logger.event_log_path {
+        eprintln!("Raw ping log saved to: {}", log_path.display());
+    }
+    report_push_drops(app);
+
+    res
+}
+
+/// Warns if push updates were dropped because the sender fell behind.
+fn report_push_drops(app: &App) {
+    if let Some(push) = &app.push
+        && push.dropped > 0
+    {
+        eprintln!(
+            "Push queue was full: {} updates to {} were dropped",
+            push.dropped, push.addr
+        );
+    }
+}
+
 /// Main application loop for live mode.
 async fn run_live_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
     loop {
@@ -282,10 +399,35 @@ async fn run_live_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) ->
         {
             use app::ViewMode;
             match app.view_mode {
+                ViewMode::List if app.prompt.is_some() => match key.code {
+                    KeyCode::Enter => app.submit_prompt(),
+                    KeyCode::Esc => app.cancel_prompt(),
+                    KeyCode::Backspace => app.prompt_backspace(),
+                    KeyCode::Char(c) => app.prompt_input(c),
+                    _ => {}
+                },
                 ViewMode::List => match key.code {
+                    KeyCode::Esc if !app.filter.is_empty() => app.clear_filter(),
                     KeyCode::Char('q') | KeyCode::Esc => app.quit(),
                     KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
                     KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+                    KeyCode::PageUp => app.page_up(),
+                    KeyCode::PageDown => app.page_down(),
+                    KeyCode::Home | KeyCode::Char('g') => app.select_first(),
+                    KeyCode::End | KeyCode::Char('G') => app.select_last(),
+                    KeyCode::Char('c') => app.toggle_compact(),
+                    KeyCode::Char(' ') => app.toggle_group(),
+                    KeyCode::Char('s') => app.cycle_sort(),
+                    KeyCode::Char('S') => app.toggle_sort_order(),
+                    KeyCode::Char('/') => app.start_filter(),
+                    KeyCode::Char('a') => app.start_add_target(),
+                    KeyCode::Char('p') => app.toggle_pause(),
+                    KeyCode::Char('P') => app.toggle_pause_all(),
+                    KeyCode::Char('+') | KeyCode::Char('=') => app.change_interval(true),
+                    KeyCode::Char('-') => app.change_interval(false),
+                    KeyCode::Char('>') => app.change_interval_all(true),
+                    KeyCode::Char('<') => app.change_interval_all(false),
+                    KeyCode::Char('d') => app.start_remove_target(),
                     KeyCode::Char('r') => app.reset_stats(),
                     KeyCode::Enter => app.show_detail(),
                     _ => {}
@@ -296,6 +438,13 @@ async fn run_live_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) ->
                     KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
                     KeyCode::Down | KeyCode::Char('j') => app.select_next(),
                     KeyCode::Char('r') => app.reset_stats(),
+                    KeyCode::Char('t') => app.toggle_trace(),
+                    KeyCode::Char('p') => app.toggle_pause(),
+                    KeyCode::Char('P') => app.toggle_pause_all(),
+                    KeyCode::Char('+') | KeyCode::Char('=') => app.change_interval(true),
+                    KeyCode::Char('-') => app.change_interval(false),
+                    KeyCode::Char('>') => app.change_interval_all(true),
+                    KeyCode::Char('<') => app.change_interval_all(false),
                     _ => {}
                 },
             }
@@ -313,7 +462,7 @@ async fn run_live_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) ->
 async fn run_replay_app<B: Backend>(
     terminal: &mut Terminal<B>,
     replay: &mut ReplayState,
-    targets: &[config::Target],
+    targets: &mut [config::Target],
     stats: &mut [stats::TargetStats],
 ) -> Result<()> {
     let mut selected: usize = 0;
@@ -340,10 +489,15 @@ async fn run_replay_app<B: Backend>(
                 KeyCode::Up | KeyCode::Char('k') => {
                     selected = selected.saturating_sub(1);
                 }
-                KeyCode::Down | KeyCode::Char('j') => {
-                    if selected < targets.len().saturating_sub(1) {
-                        selected += 1;
-                    }
+                KeyCode::Down | KeyCode::Char('j')
+                    if selected
+                        < targets
+                            .iter()
+                            .filter(|t| t.active)
+                            .count()
+                            .saturating_sub(1) =>
+                {
+                    selected += 1;
                 }
                 KeyCode::Right | KeyCode::Char('l') => replay.skip_forward(100),
                 KeyCode::Left | KeyCode::Char('h') => replay.skip_backward(100),
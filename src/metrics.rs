@@ -0,0 +1,403 @@
+use crate::config::Target;
+use crate::stats::TargetStats;
+use anyhow::{Context, Result, anyhow};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+
+/// Maximum bytes read of a scrape request's head.
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// How long a scrape may take, from connecting to the response.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Latency quantiles exported from the all-time t-digest.
+const QUANTILES: [f64; 4] = [0.5, 0.9, 0.95, 0.99];
+
+/// A scrape waiting for the app to render its metrics.
+type Scrape = oneshot::Sender<String>;
+
+/// Serves `/metrics` in OpenMetrics text format. The stats live in the app's
+/// update loop, so connections hand each scrape over a channel and the loop
+/// answers it with `answer`.
+pub struct MetricsServer {
+    /// Address the server is listening on.
+    pub addr: SocketAddr,
+    scrapes: mpsc::UnboundedReceiver<Scrape>,
+}
+
+impl MetricsServer {
+    /// Starts listening on `addr`.
+    pub async fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("cannot listen for metrics on {}", addr))?;
+        let addr = listener.local_addr()?;
+        let (tx, scrapes) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let tx = tx.clone();
+                        tokio::spawn(async move {
+                            let _ = tokio::time::timeout(SCRAPE_TIMEOUT, handle(stream, tx)).await;
+                        });
+                    }
+                    // Out of file descriptors etc.: back off instead of spinning
+                    Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+                }
+            }
+        });
+
+        Ok(Self { addr, scrapes })
+    }
+
+    /// Answers pending scrapes with the current state.
+    pub fn answer(&mut self, targets: &[Target], stats: &[TargetStats]) {
+        let mut body = None;
+        while let Ok(scrape) = self.scrapes.try_recv() {
+            let body = body.get_or_insert_with(|| render(targets, stats));
+            let _ = scrape.send(body.clone());
+        }
+    }
+}
+
+/// Serves one HTTP request.
+async fn handle(mut stream: TcpStream, scrapes: mpsc::UnboundedSender<Scrape>) -> Result<()> {
+    let head = read_head(&mut stream).await?;
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(path)) if path.split('?').next() == Some("/metrics") => {
+            let (reply, body) = oneshot::channel();
+            scrapes.send(reply)?;
+            match body.await {
+                Ok(body) => response("200 OK", CONTENT_TYPE, &body),
+                Err(_) => response("503 Service Unavailable", "text/plain", "Shutting down\n"),
+            }
+        }
+        (Some("GET"), _) => response("404 Not Found", "text/plain", "Not found\n"),
+        _ => response(
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n",
+        ),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Reads the request line and headers.
+async fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut buf = Vec::with_capacity(512);
+    let mut chunk = [0u8; 512];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.len() > MAX_REQUEST_HEAD {
+            return Err(anyhow!("request too large"));
+        }
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// A metric family with one sample per target.
+struct Metric {
+    name: &'static str,
+    kind: &'static str,
+    unit: Option<&'static str>,
+    help: &'static str,
+    /// Targets without a value are left out.
+    value: fn(&TargetStats) -> Option<f64>,
+}
+
+/// Per-target metrics besides the latency summary.
+const METRICS: [Metric; 8] = [
+    Metric {
+        name: "ptop_probes_sent",
+        kind: "counter",
+        unit: None,
+        help: "Probes sent.",
+        value: |s| Some(s.sent as f64),
+    },
+    Metric {
+        name: "ptop_probes_received",
+        kind: "counter",
+        unit: None,
+        help: "Probe replies received.",
+        value: |s| Some(s.received as f64),
+    },
+    Metric {
+        name: "ptop_loss_ratio",
+        kind: "gauge",
+        unit: Some("ratio"),
+        help: "Share of probes lost.",
+        value: |s| Some(s.packet_loss() / 100.0),
+    },
+    Metric {
+        name: "ptop_latency_min_seconds",
+        kind: "gauge",
+        unit: Some("seconds"),
+        help: "Lowest round-trip time.",
+        value: |s| secs(s.all_time.min),
+    },
+    Metric {
+        name: "ptop_latency_max_seconds",
+        kind: "gauge",
+        unit: Some("seconds"),
+        help: "Highest round-trip time.",
+        value: |s| secs(s.all_time.max),
+    },
+    Metric {
+        name: "ptop_jitter_seconds",
+        kind: "gauge",
+        unit: Some("seconds"),
+        help: "Mean round-trip time variation.",
+        value: |s| secs(s.jitter()),
+    },
+    Metric {
+        name: "ptop_mos",
+        kind: "gauge",
+        unit: None,
+        help: "Estimated Mean Opinion Score (1-5).",
+        value: |s| s.mos_score(),
+    },
+    Metric {
+        name: "ptop_paused",
+        kind: "gauge",
+        unit: None,
+        help: "Whether probing is paused.",
+        value: |s| Some(if s.paused { 1.0 } else { 0.0 }),
+    },
+];
+
+/// Renders the metrics of all active targets.
+pub fn render(targets: &[Target], stats: &[TargetStats]) -> String {
+    let active: Vec<(String, &TargetStats)> = targets
+        .iter()
+        .zip(stats)
+        .filter(|(target, _)| target.active)
+        .map(|(target, stat)| (labels(target), stat))
+        .collect();
+    let mut out = String::new();
+
+    for metric in &METRICS {
+        family(&mut out, metric.name, metric.kind, metric.unit, metric.help);
+        // Counter samples carry the _total suffix
+        let name = match metric.kind {
+            "counter" => format!("{}_total", metric.name),
+            _ => metric.name.to_string(),
+        };
+        for (labels, stat) in &active {
+            if let Some(value) = (metric.value)(stat) {
+                sample(&mut out, &name, labels, value);
+            }
+        }
+    }
+
+    let name = "ptop_latency_seconds";
+    let help = "Round-trip time of successful probes.";
+    family(&mut out, name, "summary", Some("seconds"), help);
+    for (labels, stat) in &active {
+        let all_time = &stat.all_time;
+        for q in QUANTILES {
+            if let Some(value) = secs(all_time.percentile(q)) {
+                let labels = format!("{},quantile=\"{}\"", labels, q);
+                sample(&mut out, name, &labels, value);
+            }
+        }
+        let sum = all_time.sum.as_secs_f64();
+        sample(&mut out, "ptop_latency_seconds_sum", labels, sum);
+        let count = all_time.count as f64;
+        sample(&mut out, "ptop_latency_seconds_count", labels, count);
+    }
+
+    let name = "ptop_latency_histogram_seconds";
+    family(&mut out, name, "histogram", Some("seconds"), help);
+    let (bucket, count, sum) = (
+        format!("{}_bucket", name),
+        format!("{}_count", name),
+        format!("{}_sum", name),
+    );
+    for (labels, stat) in &active {
+        let histogram = &stat.histogram;
+        let bounds = histogram.bounds_ms.as_ms();
+        for (bound, n) in bounds.iter().zip(&histogram.counts) {
+            let labels = format!("{},le=\"{}\"", labels, bound / 1000.0);
+            sample(&mut out, &bucket, &labels, *n as f64);
+        }
+        let total = histogram.count as f64;
+        sample(&mut out, &bucket, &format!("{},le=\"+Inf\"", labels), total);
+        sample(&mut out, &count, labels, total);
+        sample(&mut out, &sum, labels, histogram.sum_us as f64 / 1e6);
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+fn secs(d: Option<Duration>) -> Option<f64> {
+    d.map(|d| d.as_secs_f64())
+}
+
+/// Writes the metadata of a metric family.
+fn family(out: &mut String, name: &str, kind: &str, unit: Option<&str>, help: &str) {
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    if let Some(unit) = unit {
+        let _ = writeln!(out, "# UNIT {} {}", name, unit);
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+}
+
+fn sample(out: &mut String, name: &str, labels: &str, value: f64) {
+    let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+}
+
+/// Formats the labels identifying a target.
+fn labels(target: &Target) -> String {
+    target
+        .series_labels()
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::TargetSpec;
+    use crate::stats::PingResult;
+
+    fn sample_targets() -> (Vec<Target>, Vec<TargetStats>) {
+        let mut removed = Target::new("old", "192.0.2.1".parse().unwrap());
+        removed.active = false;
+        let targets = vec![
+            Target::new("Cloudflare \"DNS\"", "1.1.1.1".parse().unwrap()),
+            removed,
+        ];
+        let mut stats = vec![TargetStats::new(), TargetStats::new()];
+        stats[0].record(PingResult::Success(Duration::from_millis(10)));
+        stats[0].record(PingResult::Success(Duration::from_millis(30)));
+        stats[0].record(PingResult::Timeout);
+        stats[0].record(PingResult::Timeout);
+        (targets, stats)
+    }
+
+    #[test]
+    fn test_render() {
+        let (targets, stats) = sample_targets();
+        let text = render(&targets, &stats);
+        let labels = r#"target="Cloudflare \"DNS\"",address="1.1.1.1",probe="icmp""#;
+
+        assert!(text.contains(&format!("ptop_probes_sent_total{{{}}} 4\n", labels)));
+        assert!(text.contains(&format!("ptop_probes_received_total{{{}}} 2\n", labels)));
+        assert!(text.contains(&format!("ptop_loss_ratio{{{}}} 0.5\n", labels)));
+        assert!(text.contains(&format!("ptop_latency_seconds_sum{{{}}} 0.04\n", labels)));
+        assert!(text.contains(&format!("ptop_latency_seconds_count{{{}}} 2\n", labels)));
+        assert!(text.contains(&format!("ptop_latency_max_seconds{{{}}} 0.03\n", labels)));
+        assert!(text.contains(r#"quantile="0.95"} "#));
+        assert!(text.contains("# TYPE ptop_latency_seconds summary\n"));
+        let bucket = |le: &str, n: u64| {
+            format!(
+                "ptop_latency_histogram_seconds_bucket{{{},le=\"{}\"}} {}\n",
+                labels, le, n
+            )
+        };
+        assert!(text.contains(&bucket("0.005", 0)));
+        assert!(text.contains(&bucket("0.01", 1)));
+        assert!(text.contains(&bucket("0.05", 2)));
+        assert!(text.contains(&bucket("+Inf", 2)));
+        assert!(text.ends_with("# EOF\n"));
+        // Removed targets aren't exported
+        assert!(!text.contains("old"));
+    }
+
+    #[test]
+    fn test_render_rows_bound_to_different_interfaces() {
+        let mut targets = Vec::new();
+        for interface in ["eth0", "wwan0"] {
+            let mut target = Target::new("1.1.1.1", "1.1.1.1".parse().unwrap());
+            target.options.interface = Some(interface.to_string());
+            targets.push(target);
+        }
+        let tcp = TargetSpec::parse("tcp://1.1.1.1:443").unwrap();
+        targets.push(Target::from_spec("1.1.1.1", tcp));
+        let stats: Vec<TargetStats> = targets.iter().map(|_| TargetStats::new()).collect();
+
+        let text = render(&targets, &stats);
+        let sent: Vec<&str> = text
+            .lines()
+            .filter(|l| l.starts_with("ptop_probes_sent_total{"))
+            .collect();
+        assert_eq!(sent.len(), 3);
+        let unique: std::collections::HashSet<_> = sent.iter().collect();
+        assert_eq!(unique.len(), 3, "{:?}", sent);
+        assert!(sent[0].contains(r#"probe="icmp",interface="eth0""#));
+        assert!(sent[1].contains(r#"probe="icmp",interface="wwan0""#));
+        assert!(sent[2].contains(r#"probe="tcp",port="443""#));
+    }
+
+    /// Sends a raw request and returns the response.
+    async fn request(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_scrape() {
+        let (targets, stats) = sample_targets();
+        let mut server = MetricsServer::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let addr = server.addr;
+
+        let scrape = tokio::spawn(request(addr, "GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n"));
+        // Stand in for the app's update loop
+        while !scrape.is_finished() {
+            server.answer(&targets, &stats);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let response = scrape.await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.contains("ptop_probes_sent_total{"));
+        assert!(response.ends_with("# EOF\n"));
+
+        let response = request(addr, "GET / HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+        let response = request(addr, "POST /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 405"));
+    }
+}
@@ -0,0 +1,353 @@
+use crate::config::Target;
+use crate::logging::{PingEvent, SessionSummary, TargetSummary};
+use crate::stats::TargetStats;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Version of the streamed record schema. Bumped whenever a field is renamed
+/// or removed; new fields may be added without a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Columns of the CSV stream. Each row fills the columns of its record type
+/// and leaves the others empty.
+const CSV_HEADER: &str = "schema,type,timestamp,target_idx,target_name,target_addr,kind,\
+    latency_us,failure,previous_addr,sent,received,loss_pct,min_ms,avg_ms,p50_ms,p95_ms,\
+    max_ms,jitter_ms,mos,quality_grade";
+
+/// Format of the stdout stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Newline-delimited JSON, one record per line
+    Json,
+    /// CSV with a header row
+    Csv,
+}
+
+/// A streamed record, tagged with its type.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    /// A logged event, in the same shape as the raw log.
+    Event(&'a PingEvent),
+    /// A target's all-time statistics at `timestamp`.
+    Aggregate {
+        timestamp: DateTime<Utc>,
+        target_idx: usize,
+        #[serde(flatten)]
+        summary: &'a TargetSummary,
+    },
+    /// The session summary, written on exit.
+    Summary(&'a SessionSummary),
+}
+
+/// A record with the schema version.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    schema: u32,
+    #[serde(flatten)]
+    record: Record<'a>,
+}
+
+/// Streams events and periodic per-target aggregates as NDJSON or CSV.
+pub struct EventStream<W: Write = io::Stdout> {
+    format: OutputFormat,
+    writer: W,
+    /// How often per-target aggregates are written.
+    aggregate_interval: chrono::Duration,
+    /// When aggregates were last written.
+    last_aggregate_at: DateTime<Utc>,
+    /// Whether the CSV header has been written.
+    header_written: bool,
+    /// Set once the reader goes away (e.g. `ptop ... | head`).
+    closed: bool,
+}
+
+impl EventStream {
+    /// Creates a stream writing to stdout.
+    pub fn stdout(format: OutputFormat, aggregate_interval: std::time::Duration) -> Self {
+        Self::new(format, io::stdout(), aggregate_interval)
+    }
+}
+
+impl<W: Write> EventStream<W> {
+    /// Creates a stream writing to `writer`.
+    pub fn new(format: OutputFormat, writer: W, aggregate_interval: std::time::Duration) -> Self {
+        Self {
+            format,
+            writer,
+            aggregate_interval: chrono::Duration::from_std(aggregate_interval)
+                .unwrap_or(chrono::Duration::MAX),
+            last_aggregate_at: Utc::now(),
+            header_written: false,
+            closed: false,
+        }
+    }
+
+    /// Returns whether the reader has closed the stream.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Writes a logged event.
+    pub fn write_event(&mut self, event: &PingEvent) -> Result<()> {
+        match self.format {
+            OutputFormat::Json => self.write_json(Record::Event(event)),
+            OutputFormat::Csv => {
+                let row = [
+                    event.timestamp.to_rfc3339(),
+                    event.target_idx.to_string(),
+                    csv_field(&event.target_name),
+                    csv_field(&event.target_addr),
+                    label(&event.kind),
+                    opt(event.latency_us),
+                    event.failure.as_ref().map(label).unwrap_or_default(),
+                    event
+                        .previous_addr
+                        .as_deref()
+                        .map(csv_field)
+                        .unwrap_or_default(),
+                ];
+                // Aggregate columns stay empty
+                self.write_csv("event", &row.join(","), 11)
+            }
+        }
+    }
+
+    /// Writes the aggregates of all active targets if the aggregate interval
+    /// has passed. Returns true if they were written.
+    pub fn maybe_write_aggregates(
+        &mut self,
+        targets: &[Target],
+        stats: &[TargetStats],
+    ) -> Result<bool> {
+        let now = Utc::now();
+        if now.signed_duration_since(self.last_aggregate_at) < self.aggregate_interval {
+            return Ok(false);
+        }
+        self.last_aggregate_at = now;
+
+        for (idx, (target, stat)) in targets.iter().zip(stats).enumerate() {
+            if target.active {
+                self.write_aggregate(now, idx, &TargetSummary::new(target, stat))?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Writes the session summary: a single record for JSON, one `summary`
+    /// row per target for CSV.
+    pub fn write_summary(&mut self, summary: &SessionSummary) -> Result<()> {
+        match self.format {
+            OutputFormat::Json => self.write_json(Record::Summary(summary)),
+            OutputFormat::Csv => {
+                // The summary only lists active targets, without their indices
+                for target in &summary.targets {
+                    let row = aggregate_row(summary.ended, None, target);
+                    self.write_csv("summary", &row, 0)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes one target's aggregate.
+    fn write_aggregate(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        target_idx: usize,
+        summary: &TargetSummary,
+    ) -> Result<()> {
+        match self.format {
+            OutputFormat::Json => self.write_json(Record::Aggregate {
+                timestamp,
+                target_idx,
+                summary,
+            }),
+            OutputFormat::Csv => {
+                let row = aggregate_row(timestamp, Some(target_idx), summary);
+                self.write_csv("aggregate", &row, 0)
+            }
+        }
+    }
+
+    fn write_json(&mut self, record: Record) -> Result<()> {
+        let line = serde_json::to_string(&Envelope {
+            schema: SCHEMA_VERSION,
+            record,
+        })?;
+        self.write_line(&line)
+    }
+
+    /// Writes a CSV row, padding it with `empty` trailing columns.
+    fn write_csv(&mut self, kind: &str, row: &str, empty: usize) -> Result<()> {
+        if !self.header_written {
+            self.write_line(CSV_HEADER)?;
+            self.header_written = true;
+        }
+        let line = format!("{},{},{}{}", SCHEMA_VERSION, kind, row, ",".repeat(empty));
+        self.write_line(&line)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        match writeln!(self.writer, "{}", line).and_then(|_| self.writer.flush()) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                self.closed = true;
+                Ok(())
+            }
+            res => Ok(res?),
+        }
+    }
+}
+
+/// Formats the columns of an aggregate or summary row after `type`.
+fn aggregate_row(timestamp: DateTime<Utc>, target_idx: Option<usize>, t: &TargetSummary) -> String {
+    [
+        timestamp.to_rfc3339(),
+        opt(target_idx),
+        csv_field(&t.name),
+        csv_field(&t.addr),
+        // kind, latency_us, failure, previous_addr
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        t.sent.to_string(),
+        t.received.to_string(),
+        format!("{:.2}", t.loss_pct),
+        opt(t.latency_ms.min),
+        opt(t.latency_ms.avg),
+        opt(t.latency_ms.p50),
+        opt(t.latency_ms.p95),
+        opt(t.latency_ms.max),
+        opt(t.jitter_ms),
+        opt(t.mos),
+        t.quality_grade.clone().unwrap_or_default(),
+    ]
+    .join(",")
+}
+
+/// Formats an optional value, leaving the column empty for None.
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Returns the serde name of a unit enum variant, e.g. `address_changed`.
+fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::EventKind;
+    use crate::stats::{FailureKind, PingResult};
+    use std::time::Duration;
+
+    fn event(kind: EventKind, latency_us: Option<u64>) -> PingEvent {
+        PingEvent {
+            timestamp: Utc::now(),
+            target_idx: 1,
+            target_name: "Cloudflare, DNS".to_string(),
+            target_addr: "1.1.1.1".to_string(),
+            latency_us,
+            failure: latency_us.is_none().then_some(FailureKind::Timeout),
+            kind,
+            previous_addr: None,
+            options: None,
+        }
+    }
+
+    fn lines(stream: &EventStream<Vec<u8>>) -> Vec<String> {
+        String::from_utf8_lossy(&stream.writer)
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_json_stream() {
+        let mut stream = EventStream::new(OutputFormat::Json, Vec::new(), Duration::ZERO);
+        stream
+            .write_event(&event(EventKind::Ping, Some(12_000)))
+            .unwrap();
+
+        let targets = vec![Target::new("Cloudflare", "1.1.1.1".parse().unwrap())];
+        let mut stats = vec![TargetStats::new()];
+        stats[0].record(PingResult::Success(Duration::from_millis(12)));
+        assert!(stream.maybe_write_aggregates(&targets, &stats).unwrap());
+
+        let lines = lines(&stream);
+        let ping: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(ping["schema"], SCHEMA_VERSION);
+        assert_eq!(ping["type"], "event");
+        assert_eq!(ping["latency_us"], 12_000);
+        // The event part is a plain PingEvent
+        let parsed: PingEvent = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(parsed.kind, EventKind::Ping);
+
+        let aggregate: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(aggregate["type"], "aggregate");
+        assert_eq!(aggregate["target_idx"], 0);
+        assert_eq!(aggregate["name"], "Cloudflare");
+        assert_eq!(aggregate["received"], 1);
+        assert_eq!(aggregate["latency_ms"]["avg"], 12.0);
+    }
+
+    #[test]
+    fn test_csv_stream() {
+        let mut stream = EventStream::new(OutputFormat::Csv, Vec::new(), Duration::from_secs(60));
+        stream.write_event(&event(EventKind::Ping, None)).unwrap();
+        stream
+            .write_event(&event(EventKind::TargetPaused, None))
+            .unwrap();
+        // Not due yet
+        assert!(!stream.maybe_write_aggregates(&[], &[]).unwrap());
+
+        let lines = lines(&stream);
+        assert_eq!(lines.len(), 3);
+        let columns = CSV_HEADER.split(',').count();
+        assert_eq!(lines[0].split(',').count(), columns);
+        assert!(lines[1].starts_with("1,event,"));
+        assert!(lines[1].contains(",1,\"Cloudflare, DNS\",1.1.1.1,ping,,timeout,,"));
+        // One extra comma inside the quoted name
+        assert_eq!(lines[1].split(',').count(), columns + 1);
+        assert!(lines[2].contains(",target_paused,"));
+    }
+
+    #[test]
+    fn test_csv_summary_rows() {
+        let mut stream = EventStream::new(OutputFormat::Csv, Vec::new(), Duration::from_secs(60));
+        let targets = vec![Target::new("Cloudflare", "1.1.1.1".parse().unwrap())];
+        let mut stats = vec![TargetStats::new()];
+        stats[0].record(PingResult::Success(Duration::from_millis(10)));
+        stats[0].record(PingResult::Timeout);
+        let summary = SessionSummary::new(Utc::now(), Utc::now(), &targets, &stats);
+        stream.write_summary(&summary).unwrap();
+
+        let lines = lines(&stream);
+        let row: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(row.len(), CSV_HEADER.split(',').count());
+        assert_eq!(
+            row[..6],
+            ["1", "summary", row[2], "", "Cloudflare", "1.1.1.1"]
+        );
+        assert_eq!(row[10..13], ["2", "1", "50.00"]);
+    }
+}
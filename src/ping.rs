@@ -1,43 +1,195 @@
 use crate::config::Target;
-use crate::stats::PingResult;
+use crate::pmtu::PmtuSearch;
+use crate::probe::{Dscp, ProbeOptions, Prober};
+use crate::resolve::{RETRY_BACKOFF_MIN, is_hostname, next_backoff, resolve_all, resolve_host};
+use crate::stats::{FailureKind, HttpPhases, PathMtu, PingResult, SequenceEvent};
+use crate::trace::HopReply;
 use anyhow::Result;
+use std::collections::HashMap;
 use std::net::IpAddr;
-use std::time::Duration;
-use surge_ping::{Client, Config, ICMP, PingIdentifier, PingSequence};
-use tokio::sync::mpsc;
-use tokio::time::{MissedTickBehavior, interval};
-
-/// Default ping timeout.
-const PING_TIMEOUT: Duration = Duration::from_secs(4);
-
-/// Payload size for ICMP packets.
-const PAYLOAD_SIZE: usize = 56;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use surge_ping::{Client, Config, ICMP, IcmpPacket, PingIdentifier, PingSequence, SurgeError};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant as TokioInstant, MissedTickBehavior, interval, interval_at};
 
 /// Message sent from pinger to main app.
 #[derive(Debug)]
 pub struct PingUpdate {
     pub target_idx: usize,
-    pub result: PingResult,
+    pub kind: UpdateKind,
 }
 
-/// Creates the appropriate ICMP client based on IP version.
-pub async fn create_client_v4() -> Result<Client> {
-    let config = Config::default();
-    let client = Client::new(&config)?;
-    Ok(client)
+/// Payload of a pinger update.
+#[derive(Debug)]
+pub enum UpdateKind {
+    /// Result of a single probe.
+    Ping(PingResult),
+    /// The target's hostname resolved to an address (initially or after a change).
+    Resolved(IpAddr),
+    /// Resolving the target's hostname failed; will retry.
+    ResolveFailed(String),
+    /// A sequenced probe saw a late or duplicate reply.
+    Sequence(SequenceEvent),
+    /// Phase timings of an HTTP probe, sent before its result.
+    Phases(HttpPhases),
+    /// Reply to a TTL-limited probe from the target's hop discovery.
+    Hop(HopReply),
+    /// Hop discovery stopped with an error.
+    TraceFailed(String),
+    /// Progress of the target's path MTU discovery.
+    Pmtu(PathMtu),
+}
+
+/// Settings of a running pinger that can be changed from the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingerControl {
+    /// Send no probes until resumed.
+    pub paused: bool,
+    /// Time between probes.
+    pub interval: Duration,
+}
+
+/// Handle to a pinger task started by `spawn_pinger`.
+pub struct PingerHandle {
+    task: JoinHandle<()>,
+    control: watch::Sender<PingerControl>,
 }
 
-pub async fn create_client_v6() -> Result<Client> {
-    let config = Config::builder().kind(ICMP::V6).build();
-    let client = Client::new(&config)?;
+impl PingerHandle {
+    /// Stops or resumes sending probes.
+    pub fn set_paused(&self, paused: bool) {
+        self.control.send_modify(|c| c.paused = paused);
+    }
+
+    /// Changes the time between probes, starting with the next one.
+    pub fn set_interval(&self, interval: Duration) {
+        self.control.send_modify(|c| c.interval = interval);
+    }
+
+    /// Stops the pinger.
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    /// Returns whether the pinger task has ended.
+    #[cfg(test)]
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+/// Receive buffer requested for shared ICMP sockets.
+const RECV_BUFFER_SIZE: usize = 4 << 20;
+
+/// Creates an ICMP client for the given address type, bound to the probe's
+/// source address and interface, with its TTL and DSCP applied to the socket.
+fn create_client(addr: IpAddr, options: &ProbeOptions) -> Result<Client> {
+    let mut config = Config::builder().kind(match addr {
+        IpAddr::V4(_) => ICMP::V4,
+        IpAddr::V6(_) => ICMP::V6,
+    });
+    if options.source.is_some() {
+        config = config.bind(options.local_addr(addr)?);
+    }
+    let client = Client::new(&config.build())?;
+
+    #[cfg(unix)]
+    {
+        let fd = client.get_socket().get_native_sock();
+        // SAFETY: the descriptor is owned by `client`, which outlives this borrow
+        let fd = unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) };
+        options.apply_to(socket2::SockRef::from(&fd), addr)?;
+        // Replies for every target sharing this socket can arrive in one burst;
+        // best effort, the kernel caps it at net.core.rmem_max
+        let _ = socket2::SockRef::from(&fd).set_recv_buffer_size(RECV_BUFFER_SIZE);
+    }
+    #[cfg(not(unix))]
+    let _ = options;
+
     Ok(client)
 }
 
-/// Creates an ICMP client for the given address type.
-async fn create_client(addr: IpAddr) -> Result<Client> {
-    match addr {
-        IpAddr::V4(_) => create_client_v4().await,
-        IpAddr::V6(_) => create_client_v6().await,
+/// Socket-level settings; targets can only share a client if these match.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SocketKey {
+    v6: bool,
+    source: Option<IpAddr>,
+    interface: Option<String>,
+    ttl: Option<u32>,
+    dscp: Option<Dscp>,
+    pmtu: bool,
+}
+
+impl SocketKey {
+    fn new(addr: IpAddr, options: &ProbeOptions) -> Self {
+        Self {
+            v6: addr.is_ipv6(),
+            source: options.source,
+            interface: options.interface.clone(),
+            ttl: options.ttl,
+            dscp: options.dscp,
+            pmtu: options.pmtu,
+        }
+    }
+}
+
+/// ICMP clients shared by all pingers.
+///
+/// Targets with the same socket settings share one client (and socket) per
+/// address family; its receive task routes each reply to the waiting probe by
+/// identifier and sequence number. Every probe gets a process-wide unique
+/// sequence number, so targets pinging the same host never collide.
+pub struct IcmpClients {
+    clients: Mutex<HashMap<SocketKey, Arc<Client>>>,
+    ident: PingIdentifier,
+    next_seq: AtomicU16,
+}
+
+impl Default for IcmpClients {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IcmpClients {
+    pub fn new() -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            ident: PingIdentifier(rand::random()),
+            next_seq: AtomicU16::new(0),
+        }
+    }
+
+    /// Returns the client for probing `addr` with `options`, creating it on
+    /// first use.
+    fn get(&self, addr: IpAddr, options: &ProbeOptions) -> Result<Arc<Client>> {
+        let key = SocketKey::new(addr, options);
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(create_client(addr, options)?);
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Drops a client after repeated network errors so the next probe creates
+    /// a new socket. Does nothing if another probe already replaced it.
+    fn invalidate(&self, addr: IpAddr, options: &ProbeOptions, client: &Arc<Client>) {
+        let key = SocketKey::new(addr, options);
+        let mut clients = self.clients.lock().unwrap();
+        if clients.get(&key).is_some_and(|c| Arc::ptr_eq(c, client)) {
+            clients.remove(&key);
+        }
+    }
+
+    /// Returns the identifier and a fresh sequence number for the next request.
+    fn next_request(&self) -> (PingIdentifier, PingSequence) {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        (self.ident, PingSequence(seq))
     }
 }
 
@@ -53,77 +205,393 @@ fn is_network_error(err: &str) -> bool {
         || err_lower.contains("socket")
 }
 
+/// Classifies an ICMP reply that is not an echo reply (e.g. destination
+/// unreachable sent by the target itself). Returns None for echo replies.
+fn classify_reply(packet: &IcmpPacket) -> Option<FailureKind> {
+    let kind = match packet {
+        IcmpPacket::V4(p) => match (p.get_icmp_type().0, p.get_icmp_code().0) {
+            (0, _) => return None,
+            (3, 0) => FailureKind::NetUnreachable,
+            (3, 3) => FailureKind::Refused,
+            (3, 4) => FailureKind::FragNeeded,
+            (3, _) => FailureKind::HostUnreachable,
+            (11, _) => FailureKind::TtlExceeded,
+            _ => FailureKind::Other,
+        },
+        IcmpPacket::V6(p) => match (p.get_icmpv6_type().0, p.get_icmpv6_code().0) {
+            (129, _) => return None,
+            (1, 0) => FailureKind::NetUnreachable,
+            (1, 4) => FailureKind::Refused,
+            (1, _) => FailureKind::HostUnreachable,
+            (2, _) => FailureKind::FragNeeded,
+            (3, _) => FailureKind::TtlExceeded,
+            _ => FailureKind::Other,
+        },
+    };
+    Some(kind)
+}
+
+/// Classifies a surge-ping error.
+fn classify_error(err: &SurgeError) -> FailureKind {
+    match err {
+        SurgeError::Timeout { .. } => FailureKind::Timeout,
+        SurgeError::IOError(e) => FailureKind::from_io(e),
+        _ => FailureKind::Other,
+    }
+}
+
+/// Sends ICMP echo requests over the shared clients, recreating a client after
+/// repeated network errors.
+pub struct IcmpProber {
+    clients: Arc<IcmpClients>,
+    options: ProbeOptions,
+    payload: Vec<u8>,
+    consecutive_errors: u32,
+    /// Path MTU search, started on the first probe when PMTU discovery is on.
+    pmtu: Option<PmtuSearch>,
+}
+
+impl IcmpProber {
+    pub fn new(options: ProbeOptions, clients: Arc<IcmpClients>) -> Self {
+        Self {
+            clients,
+            payload: vec![0u8; options.payload_size],
+            options,
+            consecutive_errors: 0,
+            pmtu: None,
+        }
+    }
+
+    /// Restarts any path MTU search.
+    pub fn reset(&mut self) {
+        self.pmtu = None;
+    }
+
+    /// Sends the next path MTU search probe, if PMTU discovery is enabled and
+    /// the search has not finished. Once it has, regular probes use the
+    /// discovered size.
+    pub async fn discover_pmtu(&mut self, addr: IpAddr) -> Option<PathMtu> {
+        if !self.options.pmtu {
+            return None;
+        }
+        let payload_size = self.options.payload_size;
+        let size = self
+            .pmtu
+            .get_or_insert_with(|| PmtuSearch::new(addr, payload_size))
+            .next_size()?;
+
+        let result = self.echo(addr, &vec![0u8; size]).await;
+        let search = self.pmtu.as_mut()?;
+        search.record(size, &result);
+        if search.next_size().is_none() {
+            self.payload = vec![0u8; search.payload_size()];
+        }
+        Some(search.status())
+    }
+
+    /// Sends a single echo request and waits for the reply.
+    pub async fn probe(&mut self, addr: IpAddr) -> PingResult {
+        let payload = std::mem::take(&mut self.payload);
+        let result = self.echo(addr, &payload).await;
+        self.payload = payload;
+
+        // The path MTU shrank; search again from the next tick
+        if self.options.pmtu && result.failure() == Some(FailureKind::FragNeeded) {
+            self.pmtu = None;
+        }
+        result
+    }
+
+    /// Sends an echo request with the given payload and waits for the reply.
+    async fn echo(&mut self, addr: IpAddr, payload: &[u8]) -> PingResult {
+        let client = match self.clients.get(addr, &self.options) {
+            Ok(c) => c,
+            Err(e) => {
+                // Wait before retrying client creation
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let kind = e
+                    .downcast_ref::<std::io::Error>()
+                    .map_or(FailureKind::Other, FailureKind::from_io);
+                return PingResult::Error(kind, format!("Client error: {}", e));
+            }
+        };
+
+        let (ident, seq) = self.clients.next_request();
+        let mut pinger = client.pinger(addr, ident).await;
+        pinger.timeout(self.options.timeout());
+
+        match pinger.ping(seq, payload).await {
+            Ok((packet, duration)) => {
+                self.consecutive_errors = 0;
+                match classify_reply(&packet) {
+                    None => PingResult::Success(duration),
+                    Some(kind) => PingResult::Error(kind, kind.label().to_string()),
+                }
+            }
+            Err(e) => {
+                let err_str = e.to_string();
+                let kind = classify_error(&e);
+                if kind == FailureKind::Timeout || err_str.contains("timeout") {
+                    self.consecutive_errors = 0;
+                    PingResult::Timeout
+                } else {
+                    self.consecutive_errors += 1;
+                    // Recreate client after consecutive network errors
+                    if is_network_error(&err_str) && self.consecutive_errors >= 3 {
+                        self.clients.invalidate(addr, &self.options, &client);
+                        self.consecutive_errors = 0;
+                        return PingResult::Error(
+                            FailureKind::SocketRecreated,
+                            format!("{} (recreating socket)", err_str),
+                        );
+                    }
+                    PingResult::Error(kind, err_str)
+                }
+            }
+        }
+    }
+}
+
 /// Spawns a pinger task for a target.
+///
+/// Hostname targets are re-resolved every `resolve_interval` (if set), and the
+/// pinger switches to the new address when the current one is no longer returned.
+/// ICMP targets send through the shared `clients`. The returned handle
+/// pauses or stops the pinger.
 pub fn spawn_pinger(
     target_idx: usize,
     target: Target,
     ping_interval: Duration,
+    resolve_interval: Option<Duration>,
+    clients: Arc<IcmpClients>,
     tx: mpsc::UnboundedSender<PingUpdate>,
-) {
-    tokio::spawn(async move {
-        let payload = vec![0u8; PAYLOAD_SIZE];
-        let mut seq = 0u16;
-        let mut consecutive_errors = 0u32;
+) -> PingerHandle {
+    let (control, mut control_rx) = watch::channel(PingerControl {
+        paused: false,
+        interval: ping_interval,
+    });
+    let task = tokio::spawn(async move {
+        // Resolve the hostname first, retrying with backoff until it succeeds
+        let mut addr = match target.addr() {
+            Some(addr) => addr,
+            None => {
+                let mut backoff = RETRY_BACKOFF_MIN;
+                loop {
+                    match resolve_host(&target.host, target.family).await {
+                        Ok(addr) => {
+                            let kind = UpdateKind::Resolved(addr);
+                            if tx.send(PingUpdate { target_idx, kind }).is_err() {
+                                return;
+                            }
+                            break addr;
+                        }
+                        Err(e) => {
+                            let kind = UpdateKind::ResolveFailed(e.to_string());
+                            if tx.send(PingUpdate { target_idx, kind }).is_err() {
+                                return;
+                            }
+                            tokio::time::sleep(backoff).await;
+                            backoff = next_backoff(backoff);
+                        }
+                    }
+                }
+            }
+        };
+
+        let mut prober = Prober::new(&target, clients);
 
         // Use interval with skip behavior to handle slow pings gracefully
         let mut tick = interval(ping_interval);
         tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
-        let mut client: Option<Client> = None;
+        let resolve_interval =
+            resolve_interval.filter(|_| is_hostname(&target.host) && target.parent.is_none());
+        let mut last_resolved = Instant::now();
 
         loop {
-            tick.tick().await;
-
-            // Create or recreate client if needed
-            if client.is_none() {
-                match create_client(target.addr).await {
-                    Ok(c) => {
-                        client = Some(c);
-                        consecutive_errors = 0;
+            let ticked = tokio::select! {
+                _ = tick.tick() => true,
+                changed = control_rx.changed() => {
+                    if changed.is_err() {
+                        break;
                     }
-                    Err(e) => {
-                        let _ = tx.send(PingUpdate {
-                            target_idx,
-                            result: PingResult::Error(format!("Client error: {}", e)),
-                        });
-                        // Wait before retrying client creation
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                        continue;
+                    // A new interval applies from the next probe on
+                    let every = control_rx.borrow_and_update().interval;
+                    if every != tick.period() {
+                        tick = interval_at(TokioInstant::now() + every, every);
+                        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
                     }
+                    false
                 }
+            };
+
+            // Send nothing while paused, then restart the schedule (at the
+            // interval set meanwhile)
+            if control_rx.borrow().paused {
+                let every = match control_rx.wait_for(|c| !c.paused).await {
+                    Ok(control) => control.interval,
+                    Err(_) => break,
+                };
+                tick = interval(every);
+                tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                continue;
+            }
+            if !ticked {
+                continue;
             }
 
-            let c = client.as_ref().unwrap();
-            let mut pinger = c.pinger(target.addr, PingIdentifier(rand::random())).await;
-            pinger.timeout(PING_TIMEOUT);
+            // Periodically re-resolve hostnames; keep the current address on failure
+            if let Some(every) = resolve_interval
+                && last_resolved.elapsed() >= every
+            {
+                last_resolved = Instant::now();
+                if let Ok(addrs) = resolve_all(&target.host, target.family).await
+                    && !addrs.contains(&addr)
+                {
+                    let new_addr = addrs[0];
+                    if new_addr.is_ipv4() != addr.is_ipv4() {
+                        prober.reset();
+                    }
+                    addr = new_addr;
+                    let kind = UpdateKind::Resolved(addr);
+                    if tx.send(PingUpdate { target_idx, kind }).is_err() {
+                        break;
+                    }
+                }
+            }
 
-            let result = match pinger.ping(PingSequence(seq), &payload).await {
-                Ok((_, duration)) => {
-                    consecutive_errors = 0;
-                    PingResult::Success(duration)
+            if let Some(pmtu) = prober.discover_pmtu(addr).await {
+                let kind = UpdateKind::Pmtu(pmtu);
+                if tx.send(PingUpdate { target_idx, kind }).is_err() {
+                    break;
                 }
-                Err(e) => {
-                    let err_str = e.to_string();
-                    if err_str.contains("timeout") {
-                        consecutive_errors = 0;
-                        PingResult::Timeout
-                    } else {
-                        consecutive_errors += 1;
-                        // Recreate client after consecutive network errors
-                        if is_network_error(&err_str) && consecutive_errors >= 3 {
-                            client = None;
-                        }
-                        PingResult::Error(err_str)
-                    }
+                continue;
+            }
+
+            let result = prober.probe(addr).await;
+
+            for event in prober.take_sequence_events() {
+                let kind = UpdateKind::Sequence(event);
+                if tx.send(PingUpdate { target_idx, kind }).is_err() {
+                    return;
                 }
-            };
+            }
 
-            if tx.send(PingUpdate { target_idx, result }).is_err() {
+            if let Some(phases) = prober.take_phases() {
+                let kind = UpdateKind::Phases(phases);
+                if tx.send(PingUpdate { target_idx, kind }).is_err() {
+                    return;
+                }
+            }
+
+            let update = PingUpdate {
+                target_idx,
+                kind: UpdateKind::Ping(result),
+            };
+            if tx.send(update).is_err() {
                 // Channel closed, exit task
                 break;
             }
-
-            seq = seq.wrapping_add(1);
         }
     });
+    PingerHandle { task, control }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_socket_key_groups_targets() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "198.51.100.7".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let defaults = ProbeOptions::default();
+        let bigger = ProbeOptions {
+            payload_size: 1200,
+            timeout_ms: 1000,
+            ..Default::default()
+        };
+        let marked = ProbeOptions {
+            dscp: Some(Dscp(46)),
+            ..Default::default()
+        };
+
+        // Per-packet settings don't need their own socket
+        assert_eq!(
+            SocketKey::new(v4, &defaults),
+            SocketKey::new(other, &bigger)
+        );
+        assert_ne!(SocketKey::new(v4, &defaults), SocketKey::new(v6, &defaults));
+        assert_ne!(SocketKey::new(v4, &defaults), SocketKey::new(v4, &marked));
+    }
+
+    #[test]
+    fn test_next_request_is_unique() {
+        let clients = IcmpClients::new();
+        let (ident, first) = clients.next_request();
+        let (same_ident, second) = clients.next_request();
+        assert_eq!(ident, same_ident);
+        assert_ne!(first, second);
+    }
+
+    /// Starts a pinger probing a local TCP listener every `every`.
+    async fn local_pinger(every: Duration) -> (PingerHandle, mpsc::UnboundedReceiver<PingUpdate>) {
+        use crate::probe::TargetSpec;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { while listener.accept().await.is_ok() {} });
+
+        let spec = TargetSpec::parse(&format!("tcp://127.0.0.1:{}", port)).unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let pinger = spawn_pinger(
+            0,
+            Target::from_spec("local", spec),
+            every,
+            None,
+            Arc::new(IcmpClients::new()),
+            tx,
+        );
+        (pinger, rx)
+    }
+
+    /// Drains the updates received so far, returning the number of pings.
+    fn pings(rx: &mut mpsc::UnboundedReceiver<PingUpdate>) -> usize {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter(|u| matches!(u.kind, UpdateKind::Ping(_)))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_paused_pinger_stops_sending() {
+        let (pinger, mut rx) = local_pinger(Duration::from_millis(10)).await;
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(pings(&mut rx) > 0);
+
+        // Let a probe in flight finish, then expect silence
+        pinger.set_paused(true);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        pings(&mut rx);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(pings(&mut rx), 0);
+
+        pinger.set_paused(false);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(pings(&mut rx) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_pinger_interval_changes_at_runtime() {
+        let (pinger, mut rx) = local_pinger(Duration::from_secs(60)).await;
+
+        // Only the immediate first probe at a 60 s interval
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(pings(&mut rx), 1);
+
+        pinger.set_interval(Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(pings(&mut rx) >= 5);
+    }
 }
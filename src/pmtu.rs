@@ -0,0 +1,165 @@
+use crate::probe::DEFAULT_PAYLOAD_SIZE;
+use crate::stats::{FailureKind, PathMtu, PingResult};
+use std::net::IpAddr;
+
+/// ICMP echo header size in bytes.
+const ICMP_HEADER_SIZE: usize = 8;
+
+/// Link MTU assumed as the upper bound unless a larger payload is configured.
+const ETHERNET_MTU: usize = 1500;
+
+/// Probes of one size that may time out before it counts as too large
+/// (a single timeout may be ordinary loss).
+const ATTEMPTS: u32 = 3;
+
+/// Returns the IP + ICMP header overhead of an echo request to `addr`.
+pub fn header_overhead(addr: IpAddr) -> usize {
+    let ip_header = match addr {
+        IpAddr::V4(_) => 20,
+        IpAddr::V6(_) => 40,
+    };
+    ip_header + ICMP_HEADER_SIZE
+}
+
+/// Binary search for the largest Don't-Fragment payload that gets through.
+///
+/// The lower bound is assumed to pass; the largest size is tried first since
+/// most paths have no MTU problem.
+#[derive(Debug)]
+pub struct PmtuSearch {
+    /// Largest payload known to get through.
+    good: usize,
+    /// Smallest payload known not to get through.
+    bad: usize,
+    /// Whether the largest size has been tried yet.
+    tried_max: bool,
+    /// Size to probe again after a timeout, and timeouts seen at it.
+    retry: Option<usize>,
+    timeouts: u32,
+    /// Header bytes added to the payload on the wire.
+    overhead: usize,
+}
+
+impl PmtuSearch {
+    /// Starts a search towards `addr`, bounded by the configured payload size
+    /// and the Ethernet MTU.
+    pub fn new(addr: IpAddr, payload_size: usize) -> Self {
+        let overhead = header_overhead(addr);
+        let max = payload_size.max(ETHERNET_MTU - overhead);
+        Self {
+            good: payload_size.min(DEFAULT_PAYLOAD_SIZE),
+            bad: max + 1,
+            tried_max: false,
+            retry: None,
+            timeouts: 0,
+            overhead,
+        }
+    }
+
+    /// Returns the next payload size to probe, or None once the search is done.
+    pub fn next_size(&self) -> Option<usize> {
+        if self.bad - self.good <= 1 {
+            None
+        } else if let Some(size) = self.retry {
+            Some(size)
+        } else if !self.tried_max {
+            Some(self.bad - 1)
+        } else {
+            Some((self.good + self.bad) / 2)
+        }
+    }
+
+    /// Records the result of a probe of `size` bytes.
+    pub fn record(&mut self, size: usize, result: &PingResult) {
+        self.tried_max = true;
+        self.retry = None;
+        match result {
+            PingResult::Success(_) => {
+                self.good = self.good.max(size);
+                self.timeouts = 0;
+            }
+            PingResult::Error(FailureKind::FragNeeded, _) => {
+                self.bad = self.bad.min(size);
+                self.timeouts = 0;
+            }
+            _ => {
+                self.timeouts += 1;
+                if self.timeouts >= ATTEMPTS {
+                    self.bad = self.bad.min(size);
+                    self.timeouts = 0;
+                } else {
+                    self.retry = Some(size);
+                }
+            }
+        }
+    }
+
+    /// Largest payload known to get through.
+    pub fn payload_size(&self) -> usize {
+        self.good
+    }
+
+    /// Returns the path MTU found so far.
+    pub fn status(&self) -> PathMtu {
+        PathMtu {
+            mtu: self.good + self.overhead,
+            searching: self.next_size().is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Runs a search against a path that passes payloads up to `limit`.
+    fn run(search: &mut PmtuSearch, limit: usize) -> u32 {
+        let mut probes = 0;
+        while let Some(size) = search.next_size() {
+            let result = if size <= limit {
+                PingResult::Success(Duration::from_millis(1))
+            } else {
+                PingResult::Error(FailureKind::FragNeeded, String::new())
+            };
+            search.record(size, &result);
+            probes += 1;
+        }
+        probes
+    }
+
+    #[test]
+    fn test_search_finds_tunnel_mtu() {
+        let addr: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut search = PmtuSearch::new(addr, DEFAULT_PAYLOAD_SIZE);
+        let probes = run(&mut search, 1400 - 28);
+
+        assert_eq!(search.payload_size(), 1372);
+        assert_eq!(search.status().mtu, 1400);
+        assert!(!search.status().searching);
+        assert!(probes <= 12, "{}", probes);
+    }
+
+    #[test]
+    fn test_search_clean_path_takes_one_probe() {
+        let addr: IpAddr = "2001:db8::1".parse().unwrap();
+        let mut search = PmtuSearch::new(addr, DEFAULT_PAYLOAD_SIZE);
+
+        assert_eq!(run(&mut search, usize::MAX), 1);
+        assert_eq!(search.status().mtu, 1500);
+    }
+
+    #[test]
+    fn test_timeouts_retry_before_shrinking() {
+        let addr: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut search = PmtuSearch::new(addr, DEFAULT_PAYLOAD_SIZE);
+        let max = search.next_size().unwrap();
+
+        search.record(max, &PingResult::Timeout);
+        assert_eq!(search.next_size(), Some(max));
+        search.record(max, &PingResult::Timeout);
+        assert_eq!(search.next_size(), Some(max));
+        search.record(max, &PingResult::Timeout);
+        assert!(search.next_size().unwrap() < max);
+    }
+}
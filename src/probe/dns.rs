@@ -0,0 +1,355 @@
+use super::ProbeOptions;
+use crate::stats::{FailureKind, PingResult};
+use socket2::SockRef;
+use std::net::IpAddr;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+
+/// Default DNS server port.
+const DNS_PORT: u16 = 53;
+
+/// Size of the fixed DNS message header.
+const HEADER_LEN: usize = 12;
+
+/// Record types accepted by name in `type=`.
+const RECORD_TYPES: &[(&str, u16)] = &[
+    ("A", 1),
+    ("NS", 2),
+    ("CNAME", 5),
+    ("SOA", 6),
+    ("PTR", 12),
+    ("MX", 15),
+    ("TXT", 16),
+    ("AAAA", 28),
+    ("SRV", 33),
+    ("HTTPS", 65),
+];
+
+/// Response code for a name that doesn't exist; an error unless queries use
+/// random names.
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// Longest name allowed on the wire, including length bytes and the root.
+const MAX_NAME_LEN: usize = 255;
+
+/// Length of the random label prefixed to names (`ptop-xxxxxxxx.`).
+const RANDOM_LABEL_LEN: usize = 14;
+
+/// A `dns://server?name=...&type=...` probe target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQuery {
+    pub port: u16,
+    /// Name to query.
+    pub name: String,
+    /// Query type (e.g. 1 for A).
+    pub qtype: u16,
+    /// Prefix each query with a random label to bypass resolver caches.
+    /// The random names usually don't exist, so NXDOMAIN counts as a response.
+    pub random: bool,
+}
+
+impl DnsQuery {
+    /// Parses the part of a URL after the scheme, returning the query and server host.
+    pub fn parse(rest: &str) -> Result<(Self, String), String> {
+        let (authority, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (host, port) = super::split_authority(authority.trim_end_matches('/'))?;
+
+        let mut name = None;
+        let mut qtype = 1;
+        let mut random = false;
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "name" => name = Some(value.trim_end_matches('.').to_string()),
+                "type" => qtype = parse_qtype(value)?,
+                "random" => random = matches!(value, "" | "1" | "true" | "yes"),
+                other => return Err(format!("unknown dns:// parameter \"{}\"", other)),
+            }
+        }
+
+        let name = name
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| format!("missing name= in \"dns://{}\"", rest))?;
+        if name
+            .split('.')
+            .any(|label| label.is_empty() || label.len() > 63)
+        {
+            return Err(format!("invalid query name \"{}\"", name));
+        }
+        // Each label adds a length byte, the root label one more
+        let encoded_len = name.len() + 2 + if random { RANDOM_LABEL_LEN } else { 0 };
+        if encoded_len > MAX_NAME_LEN {
+            return Err(format!(
+                "query name \"{}\" is too long ({} bytes encoded{}, max {})",
+                name,
+                encoded_len,
+                if random { " with the random label" } else { "" },
+                MAX_NAME_LEN
+            ));
+        }
+
+        Ok((
+            Self {
+                port: port.unwrap_or(DNS_PORT),
+                name,
+                qtype,
+                random,
+            },
+            host,
+        ))
+    }
+}
+
+/// Parses a record type name ("AAAA") or number.
+fn parse_qtype(s: &str) -> Result<u16, String> {
+    RECORD_TYPES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        .map(|(_, code)| *code)
+        .or_else(|| s.parse().ok())
+        .ok_or_else(|| format!("unknown record type \"{}\"", s))
+}
+
+/// Builds a recursive query for `name`.
+fn encode_query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + name.len() + 6);
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf.extend_from_slice(&0x0100u16.to_be_bytes()); // RD
+    buf.extend_from_slice(&1u16.to_be_bytes()); // QDCOUNT
+    buf.extend_from_slice(&[0; 6]); // AN/NS/ARCOUNT
+    for label in name.split('.') {
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    buf.extend_from_slice(&qtype.to_be_bytes());
+    buf.extend_from_slice(&1u16.to_be_bytes()); // IN
+    buf
+}
+
+/// Parses a response header, returning its ID and response code.
+fn decode_response(buf: &[u8]) -> Option<(u16, u8)> {
+    if buf.len() < HEADER_LEN || buf[2] & 0x80 == 0 {
+        return None;
+    }
+    Some((u16::from_be_bytes([buf[0], buf[1]]), buf[3] & 0x0f))
+}
+
+/// Returns a readable name for a DNS response code.
+fn rcode_name(rcode: u8) -> String {
+    match rcode {
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        n => format!("RCODE {}", n),
+    }
+}
+
+/// Sends DNS queries over UDP and times the responses.
+pub struct DnsProber {
+    query: DnsQuery,
+    options: ProbeOptions,
+    socket: Option<UdpSocket>,
+}
+
+impl DnsProber {
+    pub fn new(query: DnsQuery, options: ProbeOptions) -> Self {
+        Self {
+            query,
+            options,
+            socket: None,
+        }
+    }
+
+    /// Drops the socket so it is recreated for the next address.
+    pub fn reset(&mut self) {
+        self.socket = None;
+    }
+
+    /// Sends one query and waits for the matching response.
+    pub async fn probe(&mut self, addr: IpAddr) -> PingResult {
+        if self.socket.is_none() {
+            let bind = match self.options.local_addr(addr) {
+                Ok(bind) => bind,
+                Err(e) => return PingResult::io_error(&e),
+            };
+            let socket = match UdpSocket::bind(bind).await {
+                Ok(s) => s,
+                Err(e) => {
+                    return PingResult::Error(
+                        FailureKind::from_io(&e),
+                        format!("Socket error: {}", e),
+                    );
+                }
+            };
+            if let Err(e) = self.options.apply_to(SockRef::from(&socket), addr) {
+                return PingResult::io_error(&e);
+            }
+            if let Err(e) = socket.connect((addr, self.query.port)).await {
+                return PingResult::io_error(&e);
+            }
+            self.socket = Some(socket);
+        }
+
+        let name = if self.query.random {
+            format!("ptop-{:08x}.{}", rand::random::<u32>(), self.query.name)
+        } else {
+            self.query.name.clone()
+        };
+        let id: u16 = rand::random();
+        let packet = encode_query(id, &name, self.query.qtype);
+
+        let start = Instant::now();
+        let socket = self.socket.as_ref().unwrap();
+        if let Err(e) = socket.send(&packet).await {
+            return PingResult::io_error(&e);
+        }
+
+        let deadline = tokio::time::Instant::from_std(start + self.options.timeout());
+        let mut buf = [0u8; 1500];
+        loop {
+            let n = match tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+                Ok(Ok(n)) => n,
+                Ok(Err(e)) => return PingResult::io_error(&e),
+                Err(_) => return PingResult::Timeout,
+            };
+
+            // Ignore late responses to earlier queries
+            let Some((reply_id, rcode)) = decode_response(&buf[..n]) else {
+                continue;
+            };
+            if reply_id != id {
+                continue;
+            }
+
+            let elapsed = start.elapsed();
+            return match rcode {
+                0 => PingResult::Success(elapsed),
+                RCODE_NXDOMAIN if self.query.random => PingResult::Success(elapsed),
+                rcode => PingResult::Error(FailureKind::BadResponse, rcode_name(rcode)),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every query with the given response code.
+    async fn stub_server(rcode: u8) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((n, peer)) = socket.recv_from(&mut buf).await {
+                let mut reply = buf[..n].to_vec();
+                reply[2] |= 0x80; // QR
+                reply[3] = (reply[3] & 0xf0) | rcode;
+                let _ = socket.send_to(&reply, peer).await;
+            }
+        });
+        port
+    }
+
+    fn query(port: u16, random: bool) -> DnsQuery {
+        DnsQuery {
+            port,
+            name: "example.com".to_string(),
+            qtype: 1,
+            random,
+        }
+    }
+
+    #[test]
+    fn test_parse_dns_query() {
+        let (q, host) = DnsQuery::parse("1.1.1.1?name=example.com&type=AAAA").unwrap();
+        assert_eq!(host, "1.1.1.1");
+        assert_eq!(q.port, 53);
+        assert_eq!(q.name, "example.com");
+        assert_eq!(q.qtype, 28);
+        assert!(!q.random);
+
+        let (q, _) = DnsQuery::parse("[::1]:5353/?name=example.com.&random=1").unwrap();
+        assert_eq!(q.port, 5353);
+        assert_eq!(q.name, "example.com");
+        assert_eq!(q.qtype, 1);
+        assert!(q.random);
+
+        assert!(DnsQuery::parse("1.1.1.1").is_err());
+        assert!(DnsQuery::parse("1.1.1.1?name=a..b").is_err());
+        assert!(DnsQuery::parse("1.1.1.1?name=a.b&type=BOGUS").is_err());
+
+        // 253 characters encode to the 255-byte limit
+        let long = [
+            "a".repeat(63),
+            "b".repeat(63),
+            "c".repeat(63),
+            "d".repeat(61),
+        ]
+        .join(".");
+        assert_eq!(long.len(), 253);
+        let (q, _) = DnsQuery::parse(&format!("1.1.1.1?name={}", long)).unwrap();
+        assert_eq!(
+            encode_query(0, &q.name, 1).len(),
+            HEADER_LEN + MAX_NAME_LEN + 4
+        );
+        // ...which leaves no room for the random label
+        let err = DnsQuery::parse(&format!("1.1.1.1?random=1&name={}", long)).unwrap_err();
+        assert!(
+            err.contains("too long (269 bytes encoded with the random label"),
+            "{}",
+            err
+        );
+        let short = &long[..long.len() - RANDOM_LABEL_LEN];
+        assert!(DnsQuery::parse(&format!("1.1.1.1?name={}&random=1", short)).is_ok());
+        assert!(DnsQuery::parse(&format!("1.1.1.1?name={}.e", long)).is_err());
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let packet = encode_query(0x1234, "a.bc", 28);
+        assert_eq!(&packet[0..2], &[0x12, 0x34]);
+        assert_eq!(
+            &packet[HEADER_LEN..],
+            &[1, b'a', 2, b'b', b'c', 0, 0, 28, 0, 1]
+        );
+        // Queries are not responses
+        assert_eq!(decode_response(&packet), None);
+    }
+
+    #[tokio::test]
+    async fn test_dns_probe_success() {
+        let port = stub_server(0).await;
+        let mut prober = DnsProber::new(query(port, false), ProbeOptions::default());
+        let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+        assert!(matches!(result, PingResult::Success(_)), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_dns_probe_error_rcodes() {
+        let port = stub_server(2).await;
+        let mut prober = DnsProber::new(query(port, false), ProbeOptions::default());
+        match prober.probe("127.0.0.1".parse().unwrap()).await {
+            PingResult::Error(kind, e) => {
+                assert_eq!(kind, FailureKind::BadResponse);
+                assert_eq!(e, "SERVFAIL");
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+
+        let port = stub_server(RCODE_NXDOMAIN).await;
+        let mut prober = DnsProber::new(query(port, false), ProbeOptions::default());
+        match prober.probe("127.0.0.1".parse().unwrap()).await {
+            PingResult::Error(_, e) => assert_eq!(e, "NXDOMAIN"),
+            other => panic!("expected error, got {:?}", other),
+        }
+
+        // Cache-busting names are expected not to exist
+        let mut prober = DnsProber::new(query(port, true), ProbeOptions::default());
+        let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+        assert!(matches!(result, PingResult::Success(_)), "{:?}", result);
+    }
+}
@@ -0,0 +1,422 @@
+use super::ProbeOptions;
+use crate::config::IpFamily;
+use crate::resolve::{is_hostname, resolve_all};
+use crate::stats::{FailureKind, HttpPhases, PingResult};
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+
+/// Maximum bytes read while looking for the status line.
+const MAX_STATUS_LINE: usize = 4096;
+
+/// HTTP request method used by the probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMethod {
+    Head,
+    Get,
+}
+
+impl HttpMethod {
+    /// Parses a method name (case-insensitive).
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_ascii_uppercase().as_str() {
+            "HEAD" => Ok(HttpMethod::Head),
+            "GET" => Ok(HttpMethod::Get),
+            _ => Err(format!(
+                "unsupported HTTP method \"{}\" (use HEAD or GET)",
+                s
+            )),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Get => "GET",
+        }
+    }
+}
+
+/// Set of HTTP status codes counted as success, e.g. "200-299,301".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusSet(Vec<(u16, u16)>);
+
+impl Default for StatusSet {
+    fn default() -> Self {
+        StatusSet(vec![(200, 399)])
+    }
+}
+
+impl StatusSet {
+    /// Parses a comma-separated list of codes and inclusive ranges.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let parse_code = |c: &str| {
+            c.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|c| (100..=599).contains(c))
+                .ok_or_else(|| format!("invalid HTTP status code \"{}\"", c.trim()))
+        };
+
+        let mut ranges = Vec::new();
+        for part in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let range = match part.split_once('-') {
+                Some((lo, hi)) => (parse_code(lo)?, parse_code(hi)?),
+                None => {
+                    let code = parse_code(part)?;
+                    (code, code)
+                }
+            };
+            if range.0 > range.1 {
+                return Err(format!("invalid status range \"{}\"", part.trim()));
+            }
+            ranges.push(range);
+        }
+
+        if ranges.is_empty() {
+            return Err("empty status code list".to_string());
+        }
+        Ok(StatusSet(ranges))
+    }
+
+    pub fn contains(&self, code: u16) -> bool {
+        self.0.iter().any(|(lo, hi)| (*lo..=*hi).contains(&code))
+    }
+}
+
+impl fmt::Display for StatusSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .0
+            .iter()
+            .map(|(lo, hi)| {
+                if lo == hi {
+                    lo.to_string()
+                } else {
+                    format!("{}-{}", lo, hi)
+                }
+            })
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+/// An `http://` or `https://` probe target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpTarget {
+    pub tls: bool,
+    pub port: u16,
+    /// Request path including any query string.
+    pub path: String,
+    /// Value of the Host header.
+    pub authority: String,
+    pub method: HttpMethod,
+    /// Status codes counted as success; anything else counts as loss.
+    pub expect: StatusSet,
+}
+
+impl HttpTarget {
+    /// Parses the part of a URL after the scheme, returning the target and host.
+    pub fn parse(rest: &str, tls: bool) -> Result<(Self, String), String> {
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('/') => (&rest[..i], rest[i..].to_string()),
+            Some(i) => (&rest[..i], format!("/{}", &rest[i..])),
+            None => (rest, "/".to_string()),
+        };
+        let (host, port) = super::split_authority(authority)?;
+        let default_port = if tls { 443 } else { 80 };
+
+        Ok((
+            Self {
+                tls,
+                port: port.unwrap_or(default_port),
+                path,
+                authority: authority.to_string(),
+                method: HttpMethod::Head,
+                expect: StatusSet::default(),
+            },
+            host,
+        ))
+    }
+}
+
+/// Returns the shared TLS client configuration (webpki root certificates).
+fn tls_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let config = ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .expect("ring provider supports default TLS versions")
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            Arc::new(config)
+        })
+        .clone()
+}
+
+/// Issues one HTTP request per probe and times each phase separately.
+pub struct HttpProber {
+    target: HttpTarget,
+    host: String,
+    family: Option<IpFamily>,
+    options: ProbeOptions,
+    /// Phase timings of the last successful request.
+    pub phases: Option<HttpPhases>,
+}
+
+impl HttpProber {
+    pub fn new(
+        target: HttpTarget,
+        host: String,
+        family: Option<IpFamily>,
+        options: ProbeOptions,
+    ) -> Self {
+        Self {
+            target,
+            host,
+            family,
+            options,
+            phases: None,
+        }
+    }
+
+    /// Sends a single request and waits for the response status line.
+    pub async fn probe(&mut self, addr: IpAddr) -> PingResult {
+        let start = Instant::now();
+        match tokio::time::timeout(self.options.timeout(), self.request(addr)).await {
+            Ok(Ok((status, phases))) => {
+                self.phases = Some(phases);
+                if self.target.expect.contains(status) {
+                    PingResult::Success(start.elapsed())
+                } else {
+                    PingResult::Error(FailureKind::BadResponse, format!("HTTP {}", status))
+                }
+            }
+            Ok(Err(e)) => {
+                let kind = e
+                    .downcast_ref::<std::io::Error>()
+                    .map_or(FailureKind::Other, FailureKind::from_io);
+                PingResult::Error(kind, e.to_string())
+            }
+            Err(_) => PingResult::Timeout,
+        }
+    }
+
+    /// Performs DNS, connect, TLS and the request, returning the status code.
+    async fn request(&self, addr: IpAddr) -> Result<(u16, HttpPhases)> {
+        // Resolve on every request like a client would, so DNS time is
+        // included. The lookup is only timed: the connection goes to this
+        // row's address, since rows expanded from one hostname each probe
+        // their own.
+        let dns = if is_hostname(&self.host) {
+            let t = Instant::now();
+            resolve_all(&self.host, self.family)
+                .await
+                .map_err(|e| anyhow!("dns: {}", e))?;
+            Some(t.elapsed())
+        } else {
+            None
+        };
+
+        let t = Instant::now();
+        let tcp =
+            super::tcp::connect(SocketAddr::new(addr, self.target.port), &self.options).await?;
+        let connect = t.elapsed();
+
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: ptop/{}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+            self.target.method.as_str(),
+            self.target.path,
+            self.target.authority,
+            env!("CARGO_PKG_VERSION"),
+        );
+
+        let (status, tls, ttfb) = if self.target.tls {
+            let t = Instant::now();
+            let server_name = ServerName::try_from(self.host.clone())?;
+            let mut stream = TlsConnector::from(tls_config())
+                .connect(server_name, tcp)
+                .await?;
+            let tls = t.elapsed();
+            let (status, ttfb) = exchange(&mut stream, request.as_bytes()).await?;
+            (status, Some(tls), ttfb)
+        } else {
+            let mut stream = tcp;
+            let (status, ttfb) = exchange(&mut stream, request.as_bytes()).await?;
+            (status, None, ttfb)
+        };
+
+        Ok((
+            status,
+            HttpPhases {
+                dns,
+                connect,
+                tls,
+                ttfb,
+            },
+        ))
+    }
+}
+
+/// Writes the request and reads the status line, returning the status code
+/// and the time from sending the request to the first response byte.
+async fn exchange<S>(stream: &mut S, request: &[u8]) -> Result<(u16, Duration)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(request).await?;
+    stream.flush().await?;
+    let sent = Instant::now();
+
+    let mut buf = Vec::with_capacity(512);
+    let mut chunk = [0u8; 512];
+    let mut ttfb = None;
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(anyhow!("connection closed before response"));
+        }
+        ttfb.get_or_insert_with(|| sent.elapsed());
+        buf.extend_from_slice(&chunk[..n]);
+
+        if let Some(end) = buf.windows(2).position(|w| w == b"\r\n") {
+            let status = parse_status_line(&buf[..end])?;
+            return Ok((status, ttfb.unwrap_or_default()));
+        }
+        if buf.len() > MAX_STATUS_LINE {
+            return Err(anyhow!("status line too long"));
+        }
+    }
+}
+
+/// Parses "HTTP/1.1 200 OK" into its status code.
+fn parse_status_line(line: &[u8]) -> Result<u16> {
+    let line = String::from_utf8_lossy(line);
+    let mut parts = line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/") => code
+            .parse()
+            .map_err(|_| anyhow!("invalid status line: {}", line)),
+        _ => Err(anyhow!("invalid status line: {}", line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Serves a fixed response to every connection.
+    async fn serve(response: &'static str) -> u16 {
+        serve_on("127.0.0.1:0".parse().unwrap(), response).await
+    }
+
+    /// Serves a fixed response to every connection on `addr`.
+    async fn serve_on(addr: SocketAddr, response: &'static str) -> u16 {
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        port
+    }
+
+    fn prober(port: u16) -> HttpProber {
+        let (target, host) =
+            HttpTarget::parse(&format!("127.0.0.1:{}/health", port), false).unwrap();
+        HttpProber::new(target, host, None, ProbeOptions::default())
+    }
+
+    #[test]
+    fn test_parse_http_target() {
+        let (t, host) = HttpTarget::parse("example.com/status?x=1", true).unwrap();
+        assert_eq!(host, "example.com");
+        assert_eq!(t.port, 443);
+        assert_eq!(t.path, "/status?x=1");
+        assert_eq!(t.authority, "example.com");
+
+        let (t, host) = HttpTarget::parse("[::1]:8080", false).unwrap();
+        assert_eq!(host, "::1");
+        assert_eq!(t.port, 8080);
+        assert_eq!(t.path, "/");
+        assert_eq!(t.authority, "[::1]:8080");
+    }
+
+    #[test]
+    fn test_status_set() {
+        let set = StatusSet::parse("200-299, 301").unwrap();
+        assert!(set.contains(204));
+        assert!(set.contains(301));
+        assert!(!set.contains(302));
+        assert_eq!(set.to_string(), "200-299,301");
+        assert!(StatusSet::parse("299-200").is_err());
+        assert!(StatusSet::parse("abc").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http_probe_success() {
+        let port = serve("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").await;
+        let mut prober = prober(port);
+
+        let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+        assert!(matches!(result, PingResult::Success(_)), "{:?}", result);
+
+        let phases = prober.phases.unwrap();
+        assert!(phases.dns.is_none());
+        assert!(phases.tls.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_http_probe_connects_to_row_address() {
+        // Two rows expanded from "localhost", each with its own server
+        let ok = "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n";
+        let port = serve_on("127.0.0.2:0".parse().unwrap(), ok).await;
+        let failing = "HTTP/1.1 503 Service Unavailable\r\n\r\n";
+        serve_on(SocketAddr::from(([127, 0, 0, 1], port)), failing).await;
+
+        let (target, host) = HttpTarget::parse(&format!("localhost:{}/", port), false).unwrap();
+        let mut prober = HttpProber::new(target, host, None, ProbeOptions::default());
+
+        let result = prober.probe("127.0.0.2".parse().unwrap()).await;
+        assert!(matches!(result, PingResult::Success(_)), "{:?}", result);
+        // The hostname is still looked up for the DNS phase
+        assert!(prober.phases.as_ref().unwrap().dns.is_some());
+
+        let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+        assert!(
+            matches!(result, PingResult::Error(FailureKind::BadResponse, _)),
+            "{:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_probe_unexpected_status() {
+        let port = serve("HTTP/1.1 503 Service Unavailable\r\n\r\n").await;
+        let mut prober = prober(port);
+
+        let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+        match result {
+            PingResult::Error(kind, e) => {
+                assert_eq!(kind, FailureKind::BadResponse);
+                assert_eq!(e, "HTTP 503");
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+mod dns;
+mod http;
+mod options;
+mod tcp;
+mod udp;
+
+use crate::config::Target;
+use crate::ping::{IcmpClients, IcmpProber};
+use crate::stats::{HttpPhases, PathMtu, PingResult, SequenceEvent};
+use dns::DnsProber;
+use http::HttpProber;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tcp::TcpProber;
+use udp::UdpProber;
+
+pub use dns::DnsQuery;
+pub use http::{HttpMethod, HttpTarget, StatusSet};
+pub use options::{DEFAULT_PAYLOAD_SIZE, Dscp, MAX_PAYLOAD_SIZE, ProbeOptions, ProbeOverrides};
+pub use udp::run_echo_server;
+
+/// How a target is probed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeKind {
+    /// ICMP echo (the default).
+    Icmp,
+    /// TCP connect (handshake) time to a port.
+    Tcp { port: u16 },
+    /// Sequenced UDP datagrams reflected by `ptop --udp-echo-server`.
+    Udp { port: u16 },
+    /// HTTP(S) request timing, split into DNS/connect/TLS/TTFB phases.
+    Http(HttpTarget),
+    /// DNS query response time from a resolver.
+    Dns(DnsQuery),
+}
+
+impl ProbeKind {
+    /// Returns true if the probe needs raw socket / ICMP privileges.
+    pub fn needs_raw_socket(&self) -> bool {
+        matches!(self, ProbeKind::Icmp)
+    }
+
+    /// Returns the URL scheme of the probe, e.g. "tcp".
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            ProbeKind::Icmp => "icmp",
+            ProbeKind::Tcp { .. } => "tcp",
+            ProbeKind::Udp { .. } => "udp",
+            ProbeKind::Http(http) if http.tls => "https",
+            ProbeKind::Http(_) => "http",
+            ProbeKind::Dns(_) => "dns",
+        }
+    }
+
+    /// Returns the destination port, if the probe uses one.
+    pub fn port(&self) -> Option<u16> {
+        match self {
+            ProbeKind::Icmp => None,
+            ProbeKind::Tcp { port } | ProbeKind::Udp { port } => Some(*port),
+            ProbeKind::Http(http) => Some(http.port),
+            ProbeKind::Dns(query) => Some(query.port),
+        }
+    }
+}
+
+/// A parsed target specification: `host` or `scheme://host:port`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetSpec {
+    /// Original text, used as the default target name.
+    pub raw: String,
+    /// Host to resolve (IP literal or hostname).
+    pub host: String,
+    pub probe: ProbeKind,
+}
+
+impl TargetSpec {
+    /// Parses a target specification.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let (probe, host) = match spec.split_once("://") {
+            None => (ProbeKind::Icmp, spec.to_string()),
+            Some((scheme, rest)) => match scheme.to_ascii_lowercase().as_str() {
+                "icmp" => (ProbeKind::Icmp, rest.to_string()),
+                "tcp" => {
+                    let (host, port) = split_host_port(rest)?;
+                    (ProbeKind::Tcp { port }, host)
+                }
+                "udp" => {
+                    let (host, port) = split_host_port(rest)?;
+                    (ProbeKind::Udp { port }, host)
+                }
+                "http" | "https" => {
+                    let (http, host) =
+                        HttpTarget::parse(rest, scheme.eq_ignore_ascii_case("https"))?;
+                    (ProbeKind::Http(http), host)
+                }
+                "dns" => {
+                    let (query, host) = DnsQuery::parse(rest)?;
+                    (ProbeKind::Dns(query), host)
+                }
+                other => return Err(format!("unsupported probe type \"{}\"", other)),
+            },
+        };
+
+        if host.is_empty() {
+            return Err(format!("missing host in \"{}\"", spec));
+        }
+
+        Ok(Self {
+            raw: spec.to_string(),
+            host,
+            probe,
+        })
+    }
+}
+
+/// Splits `host:port` or `[v6addr]:port`.
+fn split_host_port(s: &str) -> Result<(String, u16), String> {
+    let s = s.trim_end_matches('/');
+    match split_authority(s)? {
+        (host, Some(port)) => Ok((host, port)),
+        (_, None) => Err(format!("missing port in \"{}\"", s)),
+    }
+}
+
+/// Splits `host`, `host:port`, `[v6addr]` or `[v6addr]:port`.
+pub fn split_authority(s: &str) -> Result<(String, Option<u16>), String> {
+    let (host, port) = if let Some(rest) = s.strip_prefix('[') {
+        let (host, port) = rest
+            .split_once(']')
+            .ok_or_else(|| format!("unterminated IPv6 address in \"{}\"", s))?;
+        (host, port.strip_prefix(':').unwrap_or(""))
+    } else if s.matches(':').count() > 1 {
+        // Can't tell where an unbracketed IPv6 address ends
+        return Err(format!(
+            "IPv6 address in \"{}\" must be bracketed, e.g. [addr]:port",
+            s
+        ));
+    } else {
+        s.rsplit_once(':').unwrap_or((s, ""))
+    };
+
+    if port.is_empty() {
+        return Ok((host.to_string(), None));
+    }
+    let port = port
+        .parse::<u16>()
+        .ok()
+        .filter(|p| *p != 0)
+        .ok_or_else(|| format!("invalid port \"{}\"", port))?;
+    Ok((host.to_string(), Some(port)))
+}
+
+/// Sends probes of one kind to a target.
+pub enum Prober {
+    Icmp(IcmpProber),
+    Tcp(TcpProber),
+    Udp(UdpProber),
+    Http(HttpProber),
+    Dns(DnsProber),
+}
+
+impl Prober {
+    /// Creates a prober for the target's probe kind; ICMP probes send through
+    /// the shared `clients`.
+    pub fn new(target: &Target, clients: Arc<IcmpClients>) -> Self {
+        let options = target.options.clone();
+        match &target.probe {
+            ProbeKind::Icmp => Prober::Icmp(IcmpProber::new(options, clients)),
+            ProbeKind::Tcp { port } => Prober::Tcp(TcpProber::new(*port, options)),
+            ProbeKind::Udp { port } => Prober::Udp(UdpProber::new(*port, options)),
+            ProbeKind::Http(http) => Prober::Http(HttpProber::new(
+                http.clone(),
+                target.host.clone(),
+                target.family,
+                options,
+            )),
+            ProbeKind::Dns(query) => Prober::Dns(DnsProber::new(query.clone(), options)),
+        }
+    }
+
+    /// Sends a single probe and waits for its result.
+    pub async fn probe(&mut self, addr: IpAddr) -> PingResult {
+        match self {
+            Prober::Icmp(p) => p.probe(addr).await,
+            Prober::Tcp(p) => p.probe(addr).await,
+            Prober::Udp(p) => p.probe(addr).await,
+            Prober::Http(p) => p.probe(addr).await,
+            Prober::Dns(p) => p.probe(addr).await,
+        }
+    }
+
+    /// Returns sequence anomalies observed since the last call.
+    pub fn take_sequence_events(&mut self) -> Vec<SequenceEvent> {
+        match self {
+            Prober::Udp(p) => std::mem::take(&mut p.events),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the phase timings of the last request, for HTTP probes.
+    pub fn take_phases(&mut self) -> Option<HttpPhases> {
+        match self {
+            Prober::Http(p) => p.phases.take(),
+            _ => None,
+        }
+    }
+
+    /// Sends the next path MTU search probe, if PMTU discovery is enabled and
+    /// still searching. Returns the search progress, or None when the tick
+    /// should send a regular probe.
+    pub async fn discover_pmtu(&mut self, addr: IpAddr) -> Option<PathMtu> {
+        match self {
+            Prober::Icmp(p) => p.discover_pmtu(addr).await,
+            _ => None,
+        }
+    }
+
+    /// Drops any per-address state (e.g. sockets) after an address change.
+    pub fn reset(&mut self) {
+        match self {
+            Prober::Icmp(p) => p.reset(),
+            Prober::Tcp(_) | Prober::Http(_) => {}
+            Prober::Udp(p) => p.reset(),
+            Prober::Dns(p) => p.reset(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_host() {
+        let spec = TargetSpec::parse("example.com").unwrap();
+        assert_eq!(spec.host, "example.com");
+        assert_eq!(spec.probe, ProbeKind::Icmp);
+    }
+
+    #[test]
+    fn test_parse_tcp() {
+        let spec = TargetSpec::parse("tcp://example.com:443").unwrap();
+        assert_eq!(spec.host, "example.com");
+        assert_eq!(spec.probe, ProbeKind::Tcp { port: 443 });
+        assert_eq!(spec.raw, "tcp://example.com:443");
+
+        let spec = TargetSpec::parse("udp://10.0.0.2:7777").unwrap();
+        assert_eq!(spec.probe, ProbeKind::Udp { port: 7777 });
+
+        let spec = TargetSpec::parse("tcp://[2001:db8::1]:22").unwrap();
+        assert_eq!(spec.host, "2001:db8::1");
+        assert_eq!(spec.probe, ProbeKind::Tcp { port: 22 });
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(TargetSpec::parse("tcp://example.com").is_err());
+        assert!(TargetSpec::parse("tcp://example.com:0").is_err());
+        assert!(TargetSpec::parse("tcp://:443").is_err());
+        assert!(TargetSpec::parse("gopher://example.com").is_err());
+        assert!(TargetSpec::parse("https://example.com:0/").is_err());
+    }
+
+    #[test]
+    fn test_parse_unbracketed_ipv6() {
+        for spec in [
+            "tcp://::1",
+            "tcp://2001:db8::1:22",
+            "dns://2001:db8::1?name=example.com",
+            "http://::1/",
+        ] {
+            let err = TargetSpec::parse(spec).unwrap_err();
+            assert!(
+                err.contains("must be bracketed, e.g. [addr]:port"),
+                "{}",
+                err
+            );
+        }
+
+        let spec = TargetSpec::parse("dns://[2001:db8::1]?name=example.com").unwrap();
+        assert_eq!(spec.host, "2001:db8::1");
+        assert_eq!(spec.probe.port(), Some(53));
+        // Plain hosts have no port, so ICMP takes bare IPv6 addresses
+        assert_eq!(TargetSpec::parse("::1").unwrap().host, "::1");
+    }
+
+    #[test]
+    fn test_parse_http() {
+        let spec = TargetSpec::parse("https://example.com/health").unwrap();
+        assert_eq!(spec.host, "example.com");
+        match spec.probe {
+            ProbeKind::Http(http) => {
+                assert!(http.tls);
+                assert_eq!(http.port, 443);
+                assert_eq!(http.path, "/health");
+            }
+            other => panic!("expected http probe, got {:?}", other),
+        }
+
+        let spec = TargetSpec::parse("http://10.0.0.1:8080").unwrap();
+        assert_eq!(spec.probe.port(), Some(8080));
+    }
+
+    #[test]
+    fn test_parse_dns() {
+        let spec = TargetSpec::parse("dns://9.9.9.9?name=example.com&type=A").unwrap();
+        assert_eq!(spec.host, "9.9.9.9");
+        assert_eq!(spec.probe.port(), Some(53));
+        assert!(matches!(spec.probe, ProbeKind::Dns(_)));
+    }
+}
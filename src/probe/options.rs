@@ -0,0 +1,333 @@
+use serde::{Deserialize, Serialize};
+use socket2::SockRef;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// Default ICMP / UDP payload size in bytes.
+pub const DEFAULT_PAYLOAD_SIZE: usize = 56;
+
+/// Default probe timeout in milliseconds.
+pub const DEFAULT_TIMEOUT_MS: u64 = 4000;
+
+/// Largest accepted payload size (fits in a single IPv4 datagram).
+pub const MAX_PAYLOAD_SIZE: usize = 65_000;
+
+/// Named DSCP code points accepted by `Dscp::parse`.
+const DSCP_NAMES: &[(&str, u8)] = &[
+    ("BE", 0),
+    ("CS1", 8),
+    ("AF11", 10),
+    ("AF12", 12),
+    ("AF13", 14),
+    ("CS2", 16),
+    ("AF21", 18),
+    ("AF22", 20),
+    ("AF23", 22),
+    ("CS3", 24),
+    ("AF31", 26),
+    ("AF32", 28),
+    ("AF33", 30),
+    ("CS4", 32),
+    ("AF41", 34),
+    ("AF42", 36),
+    ("AF43", 38),
+    ("CS5", 40),
+    ("VA", 44),
+    ("EF", 46),
+    ("CS6", 48),
+    ("CS7", 56),
+];
+
+/// A DSCP code point (0-63), written into the upper six bits of the IPv4 TOS /
+/// IPv6 traffic class byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Dscp(pub u8);
+
+impl Dscp {
+    /// Parses a DSCP name ("EF", "AF41", "CS1", "BE") or number (0-63).
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        DSCP_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, value)| Dscp(*value))
+            .or_else(|| s.parse::<u8>().ok().filter(|v| *v < 64).map(Dscp))
+            .ok_or_else(|| {
+                format!(
+                    "invalid DSCP value \"{}\" (use e.g. EF, AF41, CS1 or 0-63)",
+                    s
+                )
+            })
+    }
+
+    /// Returns the TOS / traffic class byte.
+    pub fn tos(self) -> u32 {
+        (self.0 as u32) << 2
+    }
+}
+
+impl fmt::Display for Dscp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match DSCP_NAMES.iter().find(|(_, value)| *value == self.0) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// Per-packet settings applied by the probers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeOptions {
+    /// ICMP / UDP payload size in bytes.
+    pub payload_size: usize,
+    /// How long to wait for a reply, in milliseconds.
+    pub timeout_ms: u64,
+    /// IPv4 TTL / IPv6 hop limit (system default if unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    /// DSCP marking (unmarked if unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dscp: Option<Dscp>,
+    /// Set Don't-Fragment and search for the path MTU (ICMP only).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pmtu: bool,
+    /// Local address to send from (picked by the routing table if unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<IpAddr>,
+    /// Network interface to send through (Linux only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self {
+            payload_size: DEFAULT_PAYLOAD_SIZE,
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            ttl: None,
+            dscp: None,
+            pmtu: false,
+            source: None,
+            interface: None,
+        }
+    }
+}
+
+impl ProbeOptions {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Returns a short description, e.g. "56 B, timeout 4000 ms, TTL 32, DSCP EF".
+    pub fn summary(&self) -> String {
+        let mut parts = vec![
+            format!("{} B", self.payload_size),
+            format!("timeout {} ms", self.timeout_ms),
+        ];
+        if let Some(ttl) = self.ttl {
+            parts.push(format!("TTL {}", ttl));
+        }
+        if let Some(dscp) = self.dscp {
+            parts.push(format!("DSCP {}", dscp));
+        }
+        if self.pmtu {
+            parts.push("DF".to_string());
+        }
+        parts.join(", ")
+    }
+
+    /// Returns the local address to bind to for probing `dest`: the source
+    /// address if set, otherwise the unspecified address of `dest`'s family.
+    pub fn local_addr(&self, dest: IpAddr) -> io::Result<SocketAddr> {
+        match (self.source, dest) {
+            (Some(source), _) if source.is_ipv4() != dest.is_ipv4() => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("source address {} cannot reach {}", source, dest),
+            )),
+            (Some(source), _) => Ok(SocketAddr::new(source, 0)),
+            (None, IpAddr::V4(_)) => Ok((Ipv4Addr::UNSPECIFIED, 0).into()),
+            (None, IpAddr::V6(_)) => Ok((Ipv6Addr::UNSPECIFIED, 0).into()),
+        }
+    }
+
+    /// Applies the interface binding, TTL / hop limit, DSCP marking and
+    /// Don't-Fragment to a socket for `addr`'s family.
+    pub fn apply_to(&self, socket: SockRef<'_>, addr: IpAddr) -> io::Result<()> {
+        if let Some(interface) = &self.interface {
+            bind_interface(&socket, interface)?;
+        }
+        if self.pmtu {
+            set_dont_fragment(&socket, addr)?;
+        }
+        match addr {
+            IpAddr::V4(_) => {
+                if let Some(ttl) = self.ttl {
+                    socket.set_ttl_v4(ttl)?;
+                }
+                if let Some(dscp) = self.dscp {
+                    socket.set_tos_v4(dscp.tos())?;
+                }
+            }
+            IpAddr::V6(_) => {
+                if let Some(ttl) = self.ttl {
+                    socket.set_unicast_hops_v6(ttl)?;
+                }
+                if let Some(dscp) = self.dscp {
+                    socket.set_tclass_v6(dscp.tos())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Restricts a socket to one network interface (`SO_BINDTODEVICE`).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_interface(socket: &SockRef<'_>, interface: &str) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes())).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("cannot bind to interface {}: {}", interface, e),
+        )
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn bind_interface(_socket: &SockRef<'_>, _interface: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to an interface is only supported on Linux",
+    ))
+}
+
+/// Sets the Don't-Fragment bit on outgoing packets. Sends larger than the
+/// kernel's cached path MTU then fail with `EMSGSIZE`.
+#[cfg(target_os = "linux")]
+fn set_dont_fragment(socket: &SockRef<'_>, addr: IpAddr) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, name, value) = match addr {
+        IpAddr::V4(_) => (
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_DO,
+        ),
+        IpAddr::V6(_) => (
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_DO,
+        ),
+    };
+    // SAFETY: `value` is a valid c_int for the duration of the call
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_dont_fragment(_socket: &SockRef<'_>, _addr: IpAddr) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "path MTU discovery is only supported on Linux",
+    ))
+}
+
+/// Optional overrides of `ProbeOptions`, from the command line or config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProbeOverrides {
+    pub payload_size: Option<usize>,
+    pub timeout_ms: Option<u64>,
+    pub ttl: Option<u32>,
+    pub dscp: Option<Dscp>,
+    pub pmtu: Option<bool>,
+    pub source: Option<IpAddr>,
+    pub interface: Option<String>,
+}
+
+impl ProbeOverrides {
+    /// Overwrites the fields of `options` that are set here.
+    pub fn apply(&self, options: &mut ProbeOptions) {
+        if let Some(size) = self.payload_size {
+            options.payload_size = size;
+        }
+        if let Some(ms) = self.timeout_ms {
+            options.timeout_ms = ms;
+        }
+        if let Some(ttl) = self.ttl {
+            options.ttl = Some(ttl);
+        }
+        if let Some(dscp) = self.dscp {
+            options.dscp = Some(dscp);
+        }
+        if let Some(pmtu) = self.pmtu {
+            options.pmtu = pmtu;
+        }
+        if let Some(source) = self.source {
+            options.source = Some(source);
+        }
+        if let Some(interface) = &self.interface {
+            options.interface = Some(interface.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dscp() {
+        assert_eq!(Dscp::parse("ef"), Ok(Dscp(46)));
+        assert_eq!(Dscp::parse("AF41"), Ok(Dscp(34)));
+        assert_eq!(Dscp::parse("10"), Ok(Dscp(10)));
+        assert!(Dscp::parse("64").is_err());
+        assert!(Dscp::parse("gold").is_err());
+
+        assert_eq!(Dscp(46).tos(), 0xb8);
+        assert_eq!(Dscp(46).to_string(), "EF");
+        assert_eq!(Dscp(5).to_string(), "5");
+    }
+
+    #[test]
+    fn test_local_addr() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let mut options = ProbeOptions::default();
+        assert_eq!(options.local_addr(v6).unwrap(), "[::]:0".parse().unwrap());
+
+        options.source = Some("10.0.0.2".parse().unwrap());
+        assert_eq!(
+            options.local_addr(v4).unwrap(),
+            "10.0.0.2:0".parse().unwrap()
+        );
+        assert!(options.local_addr(v6).is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut options = ProbeOptions::default();
+        ProbeOverrides {
+            ttl: Some(8),
+            dscp: Some(Dscp(46)),
+            ..Default::default()
+        }
+        .apply(&mut options);
+
+        assert_eq!(options.payload_size, DEFAULT_PAYLOAD_SIZE);
+        assert_eq!(options.summary(), "56 B, timeout 4000 ms, TTL 8, DSCP EF");
+    }
+}
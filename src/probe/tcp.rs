@@ -0,0 +1,98 @@
+use super::ProbeOptions;
+use crate::stats::PingResult;
+use socket2::SockRef;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+use tokio::net::{TcpSocket, TcpStream};
+
+/// Connects to `dest` from the probe's source address and interface, with its
+/// TTL and DSCP applied to the socket.
+pub(super) async fn connect(dest: SocketAddr, options: &ProbeOptions) -> io::Result<TcpStream> {
+    let socket = match dest {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    options.apply_to(SockRef::from(&socket), dest.ip())?;
+    if options.source.is_some() {
+        socket.bind(options.local_addr(dest.ip())?)?;
+    }
+    socket.connect(dest).await
+}
+
+/// Measures TCP handshake time to a fixed port.
+pub struct TcpProber {
+    port: u16,
+    options: ProbeOptions,
+}
+
+impl TcpProber {
+    pub fn new(port: u16, options: ProbeOptions) -> Self {
+        Self { port, options }
+    }
+
+    /// Opens a connection and closes it as soon as the handshake completes.
+    pub async fn probe(&mut self, addr: IpAddr) -> PingResult {
+        let dest = SocketAddr::new(addr, self.port);
+        let start = Instant::now();
+
+        match tokio::time::timeout(self.options.timeout(), connect(dest, &self.options)).await {
+            Ok(Ok(stream)) => {
+                let elapsed = start.elapsed();
+                drop(stream);
+                PingResult::Success(elapsed)
+            }
+            Ok(Err(e)) => PingResult::io_error(&e),
+            Err(_) => PingResult::Timeout,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::FailureKind;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_tcp_probe_local_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { while let Ok((_stream, _)) = listener.accept().await {} });
+
+        let mut prober = TcpProber::new(port, ProbeOptions::default());
+        let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+        assert!(matches!(result, PingResult::Success(_)), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe_refused() {
+        // Bind and immediately drop to get a port with nothing listening
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        };
+
+        let mut prober = TcpProber::new(port, ProbeOptions::default());
+        let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+        assert_eq!(result.failure(), Some(FailureKind::Refused), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe_binds_source() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = tokio::spawn(async move { listener.accept().await.unwrap().1 });
+
+        let options = ProbeOptions {
+            source: Some("127.0.0.2".parse().unwrap()),
+            ..Default::default()
+        };
+        let mut prober = TcpProber::new(port, options);
+        let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+        assert!(matches!(result, PingResult::Success(_)), "{:?}", result);
+
+        let peer = accepted.await.unwrap();
+        assert_eq!(peer.ip(), "127.0.0.2".parse::<IpAddr>().unwrap());
+    }
+}
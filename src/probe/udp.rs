@@ -0,0 +1,273 @@
+use super::ProbeOptions;
+use crate::stats::{FailureKind, PingResult, SequenceEvent};
+use anyhow::Result;
+use socket2::SockRef;
+use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+use tokio::net::UdpSocket;
+
+/// Magic bytes identifying ptop UDP probe packets.
+const MAGIC: &[u8; 4] = b"PTOP";
+
+/// Size of the probe header (magic, sequence, timestamp).
+const HEADER_SIZE: usize = 16;
+
+/// Largest datagram the echo responder reflects.
+const MAX_DATAGRAM: usize = 65_536;
+
+/// How many received sequence numbers to remember for duplicate detection.
+const SEEN_WINDOW: usize = 256;
+
+/// Builds a probe datagram: magic, sequence number, send timestamp (µs),
+/// zero-padded to `size` bytes.
+fn encode_packet(seq: u32, sent_us: u64, size: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; size.max(HEADER_SIZE)];
+    buf[0..4].copy_from_slice(MAGIC);
+    buf[4..8].copy_from_slice(&seq.to_be_bytes());
+    buf[8..16].copy_from_slice(&sent_us.to_be_bytes());
+    buf
+}
+
+/// Parses a probe datagram, returning its sequence number.
+fn decode_seq(buf: &[u8]) -> Option<u32> {
+    if buf.len() < HEADER_SIZE || &buf[0..4] != MAGIC {
+        return None;
+    }
+    Some(u32::from_be_bytes(buf[4..8].try_into().ok()?))
+}
+
+/// Sends sequenced UDP datagrams to a ptop echo responder and tracks
+/// late (reordered) and duplicated replies.
+pub struct UdpProber {
+    port: u16,
+    options: ProbeOptions,
+    socket: Option<UdpSocket>,
+    /// Sequence number of the latest probe sent.
+    seq: u32,
+    /// Recently received sequence numbers.
+    seen: VecDeque<u32>,
+    epoch: Instant,
+    /// Sequence anomalies observed since the last drain.
+    pub events: Vec<SequenceEvent>,
+}
+
+impl UdpProber {
+    pub fn new(port: u16, options: ProbeOptions) -> Self {
+        Self {
+            port,
+            options,
+            socket: None,
+            seq: 0,
+            seen: VecDeque::with_capacity(SEEN_WINDOW),
+            epoch: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Drops the socket so it is recreated for the next address.
+    pub fn reset(&mut self) {
+        self.socket = None;
+    }
+
+    /// Records a received sequence number. Returns false if it was a duplicate.
+    ///
+    /// Probes are sent one at a time, so a reply to an older sequence number
+    /// arrived after its probe timed out and a later one was sent. It is
+    /// counted as reordered; its probe stays counted as lost.
+    fn mark_seen(&mut self, seq: u32) -> bool {
+        if self.seen.contains(&seq) {
+            self.events.push(SequenceEvent::Duplicate);
+            return false;
+        }
+        if self.seen.len() >= SEEN_WINDOW {
+            self.seen.pop_front();
+        }
+        self.seen.push_back(seq);
+
+        if seq < self.seq {
+            self.events.push(SequenceEvent::Reordered);
+        }
+        true
+    }
+
+    /// Sends one datagram and waits for its echo.
+    pub async fn probe(&mut self, addr: IpAddr) -> PingResult {
+        if self.socket.is_none() {
+            let bind = match self.options.local_addr(addr) {
+                Ok(bind) => bind,
+                Err(e) => return PingResult::io_error(&e),
+            };
+            let socket = match UdpSocket::bind(bind).await {
+                Ok(s) => s,
+                Err(e) => {
+                    return PingResult::Error(
+                        FailureKind::from_io(&e),
+                        format!("Socket error: {}", e),
+                    );
+                }
+            };
+            if let Err(e) = self.options.apply_to(SockRef::from(&socket), addr) {
+                return PingResult::io_error(&e);
+            }
+            if let Err(e) = socket.connect((addr, self.port)).await {
+                return PingResult::io_error(&e);
+            }
+            self.socket = Some(socket);
+        }
+
+        self.seq = self.seq.wrapping_add(1);
+        let seq = self.seq;
+        let start = Instant::now();
+        let sent_us = self.epoch.elapsed().as_micros() as u64;
+        let packet = encode_packet(seq, sent_us, self.options.payload_size);
+
+        let socket = self.socket.as_ref().unwrap();
+        if let Err(e) = socket.send(&packet).await {
+            return PingResult::io_error(&e);
+        }
+
+        let deadline = tokio::time::Instant::from_std(start + self.options.timeout());
+        // Only the header is inspected; longer echoes are truncated
+        let mut buf = [0u8; 1500];
+        loop {
+            let socket = self.socket.as_ref().unwrap();
+            let n = match tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+                Ok(Ok(n)) => n,
+                Ok(Err(e)) => return PingResult::io_error(&e),
+                Err(_) => return PingResult::Timeout,
+            };
+
+            let Some(reply_seq) = decode_seq(&buf[..n]) else {
+                continue;
+            };
+            // Replies to earlier probes are recorded but we keep waiting for ours
+            if self.mark_seen(reply_seq) && reply_seq == seq {
+                return PingResult::Success(start.elapsed());
+            }
+        }
+    }
+}
+
+/// Reflects ptop probe datagrams received on `socket` back to their sender.
+async fn serve_echo(socket: UdpSocket) -> Result<()> {
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    loop {
+        let (n, peer) = socket.recv_from(&mut buf).await?;
+        // Only reflect our own packets so the responder can't be used as a generic reflector
+        if decode_seq(&buf[..n]).is_some() {
+            let _ = socket.send_to(&buf[..n], peer).await;
+        }
+    }
+}
+
+/// Runs the UDP echo responder until interrupted.
+pub async fn run_echo_server(bind: SocketAddr) -> Result<()> {
+    let socket = UdpSocket::bind(bind).await?;
+    println!(
+        "ptop UDP echo responder listening on {} (Ctrl-C to stop)",
+        socket.local_addr()?
+    );
+
+    tokio::select! {
+        res = serve_echo(socket) => res,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn local_socket() -> (UdpSocket, u16) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        (socket, port)
+    }
+
+    #[test]
+    fn test_packet_roundtrip() {
+        let packet = encode_packet(42, 1234, 64);
+        assert_eq!(packet.len(), 64);
+        assert_eq!(encode_packet(1, 0, 0).len(), HEADER_SIZE);
+        assert_eq!(decode_seq(&packet), Some(42));
+        assert_eq!(decode_seq(b"nope"), None);
+    }
+
+    #[tokio::test]
+    async fn test_udp_probe_echo() {
+        let (socket, port) = local_socket().await;
+        tokio::spawn(serve_echo(socket));
+
+        let mut prober = UdpProber::new(port, ProbeOptions::default());
+        for _ in 0..3 {
+            let result = prober.probe("127.0.0.1".parse().unwrap()).await;
+            assert!(matches!(result, PingResult::Success(_)), "{:?}", result);
+        }
+        assert!(prober.events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_udp_probe_detects_duplicates() {
+        let (socket, port) = local_socket().await;
+        // Responder that echoes every datagram twice
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1500];
+            while let Ok((n, peer)) = socket.recv_from(&mut buf).await {
+                let _ = socket.send_to(&buf[..n], peer).await;
+                let _ = socket.send_to(&buf[..n], peer).await;
+            }
+        });
+
+        let mut prober = UdpProber::new(port, ProbeOptions::default());
+        let addr = "127.0.0.1".parse().unwrap();
+        assert!(matches!(prober.probe(addr).await, PingResult::Success(_)));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(matches!(prober.probe(addr).await, PingResult::Success(_)));
+
+        assert_eq!(prober.events, vec![SequenceEvent::Duplicate]);
+    }
+
+    #[tokio::test]
+    async fn test_udp_probe_detects_late_reply() {
+        let (socket, port) = local_socket().await;
+        // Responder that holds back the first echo past the probe timeout
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1500];
+            let mut first = true;
+            while let Ok((n, peer)) = socket.recv_from(&mut buf).await {
+                if std::mem::take(&mut first) {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                }
+                let _ = socket.send_to(&buf[..n], peer).await;
+            }
+        });
+
+        let options = ProbeOptions {
+            timeout_ms: 200,
+            ..Default::default()
+        };
+        let mut prober = UdpProber::new(port, options);
+        let addr = "127.0.0.1".parse().unwrap();
+        assert!(matches!(prober.probe(addr).await, PingResult::Timeout));
+        // The late echo of probe 1 arrives while probe 2 is waiting
+        assert!(matches!(prober.probe(addr).await, PingResult::Success(_)));
+
+        assert_eq!(prober.events, vec![SequenceEvent::Reordered]);
+    }
+
+    #[test]
+    fn test_mark_seen_reordered() {
+        let mut prober = UdpProber::new(9, ProbeOptions::default());
+        prober.seq = 3;
+        assert!(prober.mark_seen(3));
+        assert!(prober.mark_seen(2));
+        assert!(!prober.mark_seen(3));
+
+        assert_eq!(
+            prober.events,
+            vec![SequenceEvent::Reordered, SequenceEvent::Duplicate]
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use crate::config::Target;
+use crate::logging::TargetSummary;
+use crate::probe::split_authority;
+use crate::stats::{PingResult, TargetStats};
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{MissedTickBehavior, interval};
+
+/// Largest datagram sent, to stay below common path MTUs.
+const MAX_DATAGRAM: usize = 1400;
+
+/// How often a partly filled batch is sent.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lines queued for the sender before new ones are dropped.
+const QUEUE_SIZE: usize = 10_000;
+
+/// How long `finish` waits for the last batch to go out.
+const FINISH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Wire protocol of the push output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushProtocol {
+    /// StatsD (`name:value|type`).
+    Statsd,
+    /// InfluxDB line protocol over UDP.
+    Influx,
+}
+
+impl PushProtocol {
+    fn default_port(self) -> u16 {
+        match self {
+            PushProtocol::Statsd => 8125,
+            PushProtocol::Influx => 8089,
+        }
+    }
+}
+
+/// Where and how to push metrics, e.g. `statsd://127.0.0.1:8125`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushTarget {
+    pub protocol: PushProtocol,
+    pub host: String,
+    pub port: u16,
+}
+
+impl PushTarget {
+    /// Parses `statsd://host[:port]` or `influx://host[:port]`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (scheme, rest) = s.split_once("://").ok_or_else(|| {
+            format!(
+                "expected statsd://host:port or influx://host:port, got \"{}\"",
+                s
+            )
+        })?;
+        let protocol = match scheme.to_ascii_lowercase().as_str() {
+            "statsd" => PushProtocol::Statsd,
+            "influx" => PushProtocol::Influx,
+            _ => {
+                return Err(format!(
+                    "unknown push protocol \"{}\" (use statsd or influx)",
+                    scheme
+                ));
+            }
+        };
+
+        let (host, port) = split_authority(rest)?;
+        if host.is_empty() {
+            return Err(format!("missing host in \"{}\"", s));
+        }
+
+        Ok(Self {
+            protocol,
+            host,
+            port: port.unwrap_or(protocol.default_port()),
+        })
+    }
+}
+
+/// Pushes metrics over UDP, either one line per ping or per-target
+/// aggregates every `every`. Lines are queued and sent in batches by a
+/// background task, so recording never waits on the network; when the queue
+/// is full, lines are dropped.
+pub struct PushSink {
+    protocol: PushProtocol,
+    /// Destination the socket is connected to.
+    pub addr: SocketAddr,
+    lines: Option<mpsc::Sender<String>>,
+    task: Option<JoinHandle<()>>,
+    /// Interval of aggregate pushes, or None to push every ping.
+    every: Option<chrono::Duration>,
+    /// When aggregates were last pushed.
+    last_push_at: DateTime<Utc>,
+    /// Updates (a ping's or an aggregate's lines) dropped because the
+    /// queue was full.
+    pub dropped: u64,
+}
+
+impl PushSink {
+    /// Resolves the destination and starts the sender task.
+    pub async fn connect(target: &PushTarget, every: Option<Duration>) -> Result<Self> {
+        let addr = tokio::net::lookup_host((target.host.as_str(), target.port))
+            .await
+            .with_context(|| format!("cannot resolve push host {}", target.host))?
+            .next()
+            .ok_or_else(|| anyhow!("no address for push host {}", target.host))?;
+        let bind: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(addr).await?;
+
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        Ok(Self {
+            protocol: target.protocol,
+            addr,
+            lines: Some(tx),
+            task: Some(tokio::spawn(send_batches(socket, rx))),
+            every: every.map(|d| chrono::Duration::from_std(d).unwrap_or(chrono::Duration::MAX)),
+            last_push_at: Utc::now(),
+            dropped: 0,
+        })
+    }
+
+    /// Queues the lines for a ping result (per-ping mode only).
+    pub fn ping(&mut self, target: &Target, result: &PingResult) {
+        if self.every.is_none() {
+            let lines = format_ping(self.protocol, target, result, Utc::now());
+            self.queue(lines);
+        }
+    }
+
+    /// Queues the aggregates of all active targets if the push interval has
+    /// passed (aggregate mode only).
+    pub fn maybe_push_aggregates(&mut self, targets: &[Target], stats: &[TargetStats]) {
+        let Some(every) = self.every else {
+            return;
+        };
+        let now = Utc::now();
+        if now.signed_duration_since(self.last_push_at) < every {
+            return;
+        }
+        self.last_push_at = now;
+
+        for (target, stat) in targets.iter().zip(stats) {
+            if target.active {
+                let summary = TargetSummary::new(target, stat);
+                let lines = format_aggregate(self.protocol, target, &summary, now);
+                self.queue(lines);
+            }
+        }
+    }
+
+    fn queue(&mut self, lines: String) {
+        if let Some(tx) = &self.lines
+            && tx.try_send(lines).is_err()
+        {
+            self.dropped += 1;
+        }
+    }
+
+    /// Sends what is still queued and stops the sender task.
+    pub async fn finish(&mut self) {
+        // Closing the queue makes the task send its last batch and exit
+        self.lines = None;
+        if let Some(task) = self.task.take() {
+            let _ = tokio::time::timeout(FINISH_TIMEOUT, task).await;
+        }
+    }
+}
+
+/// Collects queued lines into datagrams of up to `MAX_DATAGRAM` bytes and
+/// sends them when full, every `FLUSH_INTERVAL`, and when the queue closes.
+async fn send_batches(socket: UdpSocket, mut lines: mpsc::Receiver<String>) {
+    let mut batch = String::new();
+    let mut tick = interval(FLUSH_INTERVAL);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            next = lines.recv() => {
+                let Some(next) = next else {
+                    break;
+                };
+                for line in next.lines() {
+                    if !batch.is_empty() && batch.len() + line.len() + 1 > MAX_DATAGRAM {
+                        send(&socket, &mut batch).await;
+                    }
+                    batch.push_str(line);
+                    batch.push('\n');
+                }
+            }
+            _ = tick.tick() => send(&socket, &mut batch).await,
+        }
+    }
+    send(&socket, &mut batch).await;
+}
+
+async fn send(socket: &UdpSocket, batch: &mut String) {
+    if !batch.is_empty() {
+        // Nobody listening (ICMP port unreachable) is not our problem
+        let _ = socket.send(batch.as_bytes()).await;
+        batch.clear();
+    }
+}
+
+/// Formats the lines for a single ping result.
+fn format_ping(
+    protocol: PushProtocol,
+    target: &Target,
+    result: &PingResult,
+    now: DateTime<Utc>,
+) -> String {
+    let mut out = String::new();
+    match protocol {
+        PushProtocol::Statsd => {
+            let prefix = statsd_prefix(target);
+            let _ = writeln!(out, "{}.sent:1|c", prefix);
+            match result {
+                PingResult::Success(d) => {
+                    let _ = writeln!(out, "{}.rtt:{}|ms", prefix, d.as_secs_f64() * 1000.0);
+                }
+                _ => {
+                    let _ = writeln!(out, "{}.lost:1|c", prefix);
+                }
+            }
+        }
+        PushProtocol::Influx => {
+            let fields = match result {
+                PingResult::Success(d) => format!("rtt_ms={},lost=false", d.as_secs_f64() * 1000.0),
+                _ => {
+                    let failure = serde_json::to_string(&result.failure()).unwrap_or_default();
+                    format!("lost=true,failure={}", failure)
+                }
+            };
+            let _ = writeln!(
+                out,
+                "ptop_ping,{} {} {}",
+                influx_tags(target),
+                fields,
+                timestamp_ns(now)
+            );
+        }
+    }
+    out
+}
+
+/// Formats the lines for a target's aggregate.
+fn format_aggregate(
+    protocol: PushProtocol,
+    target: &Target,
+    t: &TargetSummary,
+    now: DateTime<Utc>,
+) -> String {
+    let values = [
+        ("sent", Some(t.sent as f64)),
+        ("received", Some(t.received as f64)),
+        ("loss_pct", Some(t.loss_pct)),
+        ("min_ms", t.latency_ms.min),
+        ("avg_ms", t.latency_ms.avg),
+        ("p50_ms", t.latency_ms.p50),
+        ("p95_ms", t.latency_ms.p95),
+        ("max_ms", t.latency_ms.max),
+        ("jitter_ms", t.jitter_ms),
+        ("mos", t.mos),
+    ];
+    let values = values.iter().filter_map(|(k, v)| v.map(|v| (k, v)));
+
+    let mut out = String::new();
+    match protocol {
+        PushProtocol::Statsd => {
+            let prefix = statsd_prefix(target);
+            for (key, value) in values {
+                let _ = writeln!(out, "{}.{}:{}|g", prefix, key, value);
+            }
+        }
+        PushProtocol::Influx => {
+            let fields: Vec<String> = values
+                .map(|(key, value)| match *key {
+                    "sent" | "received" => format!("{}={}i", key, value),
+                    _ => format!("{}={}", key, value),
+                })
+                .collect();
+            let _ = writeln!(
+                out,
+                "ptop_summary,{} {} {}",
+                influx_tags(target),
+                fields.join(","),
+                timestamp_ns(now)
+            );
+        }
+    }
+    out
+}
+
+/// Returns the StatsD path of a target: `ptop`, the name, address and
+/// probe, then `port_`, `interface_` and `source_` segments where set, so
+/// rows that share a name stay apart.
+fn statsd_prefix(target: &Target) -> String {
+    let mut prefix = String::from("ptop");
+    for (key, value) in target.series_labels() {
+        match key {
+            _ if value.is_empty() => {}
+            "target" | "address" | "probe" => {
+                let _ = write!(prefix, ".{}", statsd_name(&value));
+            }
+            _ => {
+                let _ = write!(prefix, ".{}_{}", key, statsd_name(&value));
+            }
+        }
+    }
+    prefix
+}
+
+/// Replaces characters StatsD treats specially (and dots, which would nest
+/// the metric) with underscores.
+fn statsd_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Formats the tags of an InfluxDB line from the target's series labels,
+/// leaving out an empty address.
+fn influx_tags(target: &Target) -> String {
+    let escape = |v: &str| {
+        v.replace('\\', "\\\\")
+            .replace(',', "\\,")
+            .replace('=', "\\=")
+            .replace(' ', "\\ ")
+    };
+    target
+        .series_labels()
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| format!("{}={}", key, escape(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn timestamp_ns(now: DateTime<Utc>) -> i64 {
+    now.timestamp_nanos_opt().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::TargetSpec;
+    use crate::stats::FailureKind;
+
+    #[test]
+    fn test_parse_push_target() {
+        let t = PushTarget::parse("statsd://localhost").unwrap();
+        assert_eq!(t.protocol, PushProtocol::Statsd);
+        assert_eq!((t.host.as_str(), t.port), ("localhost", 8125));
+
+        let t = PushTarget::parse("influx://[::1]:9000").unwrap();
+        assert_eq!(t.protocol, PushProtocol::Influx);
+        assert_eq!((t.host.as_str(), t.port), ("::1", 9000));
+
+        assert!(PushTarget::parse("127.0.0.1:8125").is_err());
+        assert!(PushTarget::parse("graphite://host").is_err());
+        assert!(PushTarget::parse("statsd://host:x").is_err());
+        assert!(PushTarget::parse("statsd://host:0").is_err());
+        let err = PushTarget::parse("influx://::1").unwrap_err();
+        assert!(err.contains("must be bracketed"), "{}", err);
+    }
+
+    #[test]
+    fn test_format_lines() {
+        let target = Target::new("Cloudflare DNS", "1.1.1.1".parse().unwrap());
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let ok = PingResult::Success(Duration::from_micros(12_500));
+        let failed = PingResult::Error(FailureKind::HostUnreachable, "unreachable".into());
+
+        assert_eq!(
+            format_ping(PushProtocol::Statsd, &target, &ok, now),
+            "ptop.Cloudflare_DNS.1_1_1_1.icmp.sent:1|c\nptop.Cloudflare_DNS.1_1_1_1.icmp.rtt:12.5|ms\n"
+        );
+        assert_eq!(
+            format_ping(PushProtocol::Statsd, &target, &failed, now),
+            "ptop.Cloudflare_DNS.1_1_1_1.icmp.sent:1|c\nptop.Cloudflare_DNS.1_1_1_1.icmp.lost:1|c\n"
+        );
+        assert_eq!(
+            format_ping(PushProtocol::Influx, &target, &ok, now),
+            "ptop_ping,target=Cloudflare\\ DNS,address=1.1.1.1,probe=icmp rtt_ms=12.5,lost=false 1700000000000000000\n"
+        );
+        assert_eq!(
+            format_ping(PushProtocol::Influx, &target, &failed, now),
+            "ptop_ping,target=Cloudflare\\ DNS,address=1.1.1.1,probe=icmp lost=true,failure=\"host_unreachable\" 1700000000000000000\n"
+        );
+
+        let mut stats = TargetStats::new();
+        stats.record(ok);
+        stats.record(failed);
+        let summary = TargetSummary::new(&target, &stats);
+        let influx = format_aggregate(PushProtocol::Influx, &target, &summary, now);
+        assert!(influx.starts_with("ptop_summary,target=Cloudflare\\ DNS,address=1.1.1.1,probe=icmp sent=2i,received=1i,loss_pct=50,min_ms=12.5,"));
+        assert!(!influx.contains("jitter_ms"));
+        let statsd = format_aggregate(PushProtocol::Statsd, &target, &summary, now);
+        assert!(statsd.contains("ptop.Cloudflare_DNS.1_1_1_1.icmp.loss_pct:50|g\n"));
+    }
+
+    #[test]
+    fn test_rows_sharing_a_name_stay_apart() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let ok = PingResult::Success(Duration::from_millis(5));
+        let mut tcp = Target::from_spec("1.1.1.1", TargetSpec::parse("tcp://1.1.1.1:443").unwrap());
+        tcp.options.interface = Some("wwan0".into());
+        tcp.options.source = Some("100.64.0.2".parse().unwrap());
+
+        assert_eq!(
+            format_ping(PushProtocol::Statsd, &tcp, &ok, now)
+                .lines()
+                .next(),
+            Some("ptop.1_1_1_1.1_1_1_1.tcp.port_443.interface_wwan0.source_100_64_0_2.sent:1|c")
+        );
+        assert!(
+            format_ping(PushProtocol::Influx, &tcp, &ok, now).starts_with(
+                "ptop_ping,target=1.1.1.1,address=1.1.1.1,probe=tcp,port=443,interface=wwan0,source=100.64.0.2 "
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_batches_over_udp() {
+        let listener = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let push = PushTarget::parse(&format!("statsd://127.0.0.1:{}", port)).unwrap();
+        let mut sink = PushSink::connect(&push, None).await.unwrap();
+
+        let target = Target::new("local", "127.0.0.1".parse().unwrap());
+        for _ in 0..3 {
+            sink.ping(&target, &PingResult::Success(Duration::from_millis(1)));
+        }
+        sink.finish().await;
+
+        // All pings arrive in one datagram
+        let mut buf = [0u8; MAX_DATAGRAM];
+        let n = tokio::time::timeout(Duration::from_secs(2), listener.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        let datagram = String::from_utf8_lossy(&buf[..n]);
+        assert_eq!(
+            datagram
+                .matches("ptop.local.127_0_0_1.icmp.sent:1|c\n")
+                .count(),
+            3
+        );
+        assert_eq!(
+            datagram
+                .matches("ptop.local.127_0_0_1.icmp.rtt:1|ms\n")
+                .count(),
+            3
+        );
+        assert_eq!(sink.dropped, 0);
+    }
+}
@@ -1,9 +1,10 @@
-use crate::config::Target;
-use crate::logging::{PingEvent, load_events};
-use crate::stats::{PingResult, TargetStats};
+use crate::config::{ResolveState, Target};
+use crate::logging::{EventKind, PingEvent, load_events};
+use crate::stats::{FailureKind, PingResult, TargetStats};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -159,39 +160,231 @@ impl ReplayState {
     }
 }
 
-/// Builds targets and initial stats from replay events.
+/// Builds targets and initial stats from replay events. Rows are indexed by
+/// the logged `target_idx`, like the targets they were recorded from, so rows
+/// sharing a name or address (different probes, links or source addresses)
+/// stay apart.
 pub fn build_replay_targets(events: &[PingEvent]) -> (Vec<Target>, Vec<TargetStats>) {
-    let mut target_map: HashMap<(String, String), usize> = HashMap::new();
-    let mut targets = Vec::new();
-    let mut stats = Vec::new();
+    let len = events.iter().map(|e| e.target_idx + 1).max().unwrap_or(0);
+    let mut names: Vec<Option<&str>> = vec![None; len];
+    let mut addrs: Vec<Option<IpAddr>> = vec![None; len];
+    let mut options = HashMap::new();
+    // Targets added at runtime are hidden until their add event is replayed
+    let mut added = HashSet::new();
 
     for event in events {
-        let key = (event.target_name.clone(), event.target_addr.clone());
-        if let std::collections::hash_map::Entry::Vacant(e) = target_map.entry(key) {
-            let idx = targets.len();
-            e.insert(idx);
-
-            if let Ok(addr) = event.target_addr.parse() {
-                targets.push(Target::new(event.target_name.clone(), addr));
-                stats.push(TargetStats::new());
+        let idx = event.target_idx;
+        names[idx].get_or_insert(&event.target_name);
+        match event.kind {
+            EventKind::TargetInfo | EventKind::TargetAdded => {
+                if let Some(opts) = &event.options {
+                    options.entry(idx).or_insert_with(|| opts.clone());
+                }
+                if event.kind == EventKind::TargetAdded {
+                    added.insert(idx);
+                }
             }
+            EventKind::Ping if addrs[idx].is_none() => {
+                addrs[idx] = event.target_addr.parse().ok();
+            }
+            _ => {}
         }
     }
 
+    let targets = (0..len)
+        .map(|idx| {
+            let name = names[idx].unwrap_or_default();
+            // Targets that never resolved are shown as such
+            let mut target = match addrs[idx] {
+                Some(addr) => Target::new(name, addr),
+                None => Target::from_host(name, ""),
+            };
+            if let Some(opts) = options.remove(&idx) {
+                target.options = opts;
+            }
+            // Indices without events belong to no target
+            target.active = names[idx].is_some() && !added.contains(&idx);
+            target
+        })
+        .collect();
+    let stats = (0..len).map(|_| TargetStats::new()).collect();
+
     (targets, stats)
 }
 
-/// Applies a replay event to the appropriate stats.
-pub fn apply_event(event: &PingEvent, targets: &[Target], stats: &mut [TargetStats]) {
-    // Find the target by address
-    for (idx, target) in targets.iter().enumerate() {
-        if target.addr.to_string() == event.target_addr {
-            let result = match event.latency_us {
-                Some(us) => PingResult::Success(Duration::from_micros(us)),
-                None => PingResult::Timeout,
+/// Applies a replay event to the target it was logged for.
+pub fn apply_event(event: &PingEvent, targets: &mut [Target], stats: &mut [TargetStats]) {
+    let idx = event.target_idx;
+    let (Some(target), Some(stat)) = (targets.get_mut(idx), stats.get_mut(idx)) else {
+        return;
+    };
+    match event.kind {
+        EventKind::Ping => {
+            if target.active {
+                let result = match (event.latency_us, event.failure) {
+                    (Some(us), _) => PingResult::Success(Duration::from_micros(us)),
+                    (None, None | Some(FailureKind::Timeout)) => PingResult::Timeout,
+                    (None, Some(kind)) => PingResult::Error(kind, kind.label().to_string()),
+                };
+                stat.record(result);
+            }
+        }
+        EventKind::AddressChanged => {
+            let Ok(addr) = event.target_addr.parse() else {
+                return;
             };
-            stats[idx].record(result);
-            break;
+            if let Some(from) = target.addr() {
+                stat.record_addr_change(from, addr);
+            }
+            target.state = ResolveState::Resolved(addr);
+        }
+        EventKind::TargetAdded | EventKind::TargetRemoved => {
+            target.active = event.kind == EventKind::TargetAdded;
+        }
+        EventKind::TargetPaused | EventKind::TargetResumed => {
+            stat.set_paused(event.kind == EventKind::TargetPaused);
+        }
+        // Applied when building the targets
+        EventKind::TargetInfo => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses raw log lines into events.
+    fn events(lines: &[&str]) -> Vec<PingEvent> {
+        lines
+            .iter()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_event_replays_failure_kind() {
+        let events = events(&[
+            r#"{"timestamp":"2024-01-01T00:00:00Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":1500}"#,
+            r#"{"timestamp":"2024-01-01T00:00:01Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":null,"failure":"host_unreachable"}"#,
+            // Logs written before failure kinds existed
+            r#"{"timestamp":"2024-01-01T00:00:02Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":null}"#,
+        ]);
+
+        let (mut targets, mut stats) = build_replay_targets(&events);
+        for event in &events {
+            apply_event(event, &mut targets, &mut stats);
+        }
+
+        assert_eq!(stats[0].received, 1);
+        assert_eq!(
+            stats[0].failures.get(&FailureKind::HostUnreachable),
+            Some(&1)
+        );
+        assert_eq!(stats[0].failures.get(&FailureKind::Timeout), Some(&1));
+    }
+
+    #[test]
+    fn test_build_replay_targets_restores_options() {
+        let events = events(&[
+            r#"{"timestamp":"2024-01-01T00:00:00Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":null,"kind":"target_info","options":{"payload_size":1200,"timeout_ms":2000,"dscp":46}}"#,
+            r#"{"timestamp":"2024-01-01T00:00:01Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":1500}"#,
+        ]);
+
+        let (mut targets, mut stats) = build_replay_targets(&events);
+        for event in &events {
+            apply_event(event, &mut targets, &mut stats);
+        }
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].options.payload_size, 1200);
+        assert_eq!(
+            targets[0].options.summary(),
+            "1200 B, timeout 2000 ms, DSCP EF"
+        );
+        assert_eq!(stats[0].sent, 1);
+    }
+
+    #[test]
+    fn test_replay_target_lifecycle() {
+        let events = events(&[
+            r#"{"timestamp":"2024-01-01T00:00:00Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":1500}"#,
+            r#"{"timestamp":"2024-01-01T00:00:01Z","target_idx":1,"target_name":"example.com","target_addr":"","latency_us":null,"kind":"target_added","options":{"payload_size":100,"timeout_ms":4000}}"#,
+            r#"{"timestamp":"2024-01-01T00:00:02Z","target_idx":1,"target_name":"example.com","target_addr":"192.0.2.2","latency_us":2500}"#,
+            r#"{"timestamp":"2024-01-01T00:00:03Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":null,"kind":"target_removed"}"#,
+        ]);
+
+        let (mut targets, mut stats) = build_replay_targets(&events);
+        assert_eq!(targets.len(), 2);
+        assert!(targets[0].active);
+        assert!(!targets[1].active);
+        assert_eq!(targets[1].options.payload_size, 100);
+
+        apply_event(&events[0], &mut targets, &mut stats);
+        apply_event(&events[1], &mut targets, &mut stats);
+        assert!(targets[1].active);
+
+        apply_event(&events[2], &mut targets, &mut stats);
+        apply_event(&events[3], &mut targets, &mut stats);
+        assert!(!targets[0].active);
+        assert_eq!(stats[1].received, 1);
+    }
+
+    #[test]
+    fn test_replay_probes_to_same_address() {
+        let events = events(&[
+            r#"{"timestamp":"2024-01-01T00:00:00Z","target_idx":0,"target_name":"1.1.1.1","target_addr":"1.1.1.1","latency_us":1500}"#,
+            r#"{"timestamp":"2024-01-01T00:00:00Z","target_idx":1,"target_name":"tcp://1.1.1.1:443","target_addr":"1.1.1.1","latency_us":null}"#,
+            r#"{"timestamp":"2024-01-01T00:00:01Z","target_idx":1,"target_name":"tcp://1.1.1.1:443","target_addr":"1.1.1.1","latency_us":4000}"#,
+        ]);
+
+        let (mut targets, mut stats) = build_replay_targets(&events);
+        for event in &events {
+            apply_event(event, &mut targets, &mut stats);
         }
+
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[1].name, "tcp://1.1.1.1:443");
+        assert_eq!((stats[0].sent, stats[0].received), (1, 1));
+        assert_eq!((stats[1].sent, stats[1].received), (2, 1));
+    }
+
+    #[test]
+    fn test_replay_rows_bound_to_different_links() {
+        let events = events(&[
+            r#"{"timestamp":"2024-01-01T00:00:00Z","target_idx":0,"target_name":"1.1.1.1","target_addr":"1.1.1.1","latency_us":null,"kind":"target_info","options":{"payload_size":56,"timeout_ms":1000,"interface":"eth0"}}"#,
+            r#"{"timestamp":"2024-01-01T00:00:00Z","target_idx":1,"target_name":"1.1.1.1","target_addr":"1.1.1.1","latency_us":null,"kind":"target_info","options":{"payload_size":56,"timeout_ms":1000,"source":"100.64.0.2","interface":"wwan0"}}"#,
+            r#"{"timestamp":"2024-01-01T00:00:01Z","target_idx":0,"target_name":"1.1.1.1","target_addr":"1.1.1.1","latency_us":1500}"#,
+            r#"{"timestamp":"2024-01-01T00:00:01Z","target_idx":1,"target_name":"1.1.1.1","target_addr":"1.1.1.1","latency_us":null}"#,
+        ]);
+
+        let (mut targets, mut stats) = build_replay_targets(&events);
+        for event in &events {
+            apply_event(event, &mut targets, &mut stats);
+        }
+
+        assert_eq!(targets[0].addr_label(), "1.1.1.1 via eth0");
+        assert_eq!(targets[1].addr_label(), "1.1.1.1 from 100.64.0.2 via wwan0");
+        assert_eq!(stats[0].received, 1);
+        assert_eq!((stats[1].sent, stats[1].received), (1, 0));
+    }
+
+    #[test]
+    fn test_replay_pause_gaps() {
+        let events = events(&[
+            r#"{"timestamp":"2024-01-01T00:00:00Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":1500}"#,
+            r#"{"timestamp":"2024-01-01T00:00:01Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":null,"kind":"target_paused"}"#,
+            r#"{"timestamp":"2024-01-01T00:01:00Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":null,"kind":"target_resumed"}"#,
+            r#"{"timestamp":"2024-01-01T00:01:01Z","target_idx":0,"target_name":"a","target_addr":"192.0.2.1","latency_us":1500}"#,
+        ]);
+
+        let (mut targets, mut stats) = build_replay_targets(&events);
+        for event in &events {
+            apply_event(event, &mut targets, &mut stats);
+        }
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(stats[0].sent, 2);
+        assert_eq!(stats[0].pause_columns(10), vec![1]);
+        assert!(!stats[0].paused);
     }
 }
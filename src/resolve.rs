@@ -0,0 +1,148 @@
+use crate::config::{IpFamily, ResolveState, Target};
+use anyhow::{Result, anyhow};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Delay before the first retry of a failed resolution.
+pub const RETRY_BACKOFF_MIN: Duration = Duration::from_secs(1);
+
+/// Upper bound for the resolution retry delay.
+pub const RETRY_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Resolves a host (IP literal or hostname) to all of its addresses,
+/// optionally restricted to one address family.
+pub async fn resolve_all(host: &str, family: Option<IpFamily>) -> Result<Vec<IpAddr>> {
+    if let Some(addr) = parse_literal(host) {
+        return Ok(vec![addr]);
+    }
+
+    let mut addrs: Vec<IpAddr> = Vec::new();
+    for sock_addr in tokio::net::lookup_host((host, 0)).await? {
+        let addr = sock_addr.ip();
+        if family.is_none_or(|f| f == IpFamily::of(&addr)) && !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+
+    if addrs.is_empty() {
+        return Err(match family {
+            Some(f) => anyhow!("no {} addresses found for {}", f.label(), host),
+            None => anyhow!("no addresses found for {}", host),
+        });
+    }
+    Ok(addrs)
+}
+
+/// Resolves a host (IP literal or hostname) to its first address.
+pub async fn resolve_host(host: &str, family: Option<IpFamily>) -> Result<IpAddr> {
+    Ok(resolve_all(host, family).await?[0])
+}
+
+/// Expands targets with `all_addrs` set into one row per resolved address,
+/// IPv4 first. Targets that fail to resolve are kept as a single row.
+pub async fn expand_targets(targets: Vec<Target>) -> Vec<Target> {
+    let mut expanded = Vec::with_capacity(targets.len());
+
+    for target in targets {
+        if !target.all_addrs || !is_hostname(&target.host) {
+            expanded.push(target);
+            continue;
+        }
+
+        match resolve_all(&target.host, target.family).await {
+            Ok(mut addrs) => {
+                addrs.sort_by_key(|a| a.is_ipv6());
+                for addr in addrs {
+                    let mut row = target.clone();
+                    row.state = ResolveState::Resolved(addr);
+                    row.parent = Some(target.name.clone());
+                    expanded.push(row);
+                }
+            }
+            Err(_) => expanded.push(target),
+        }
+    }
+
+    expanded
+}
+
+/// Returns true if the host is a hostname that needs DNS resolution.
+pub fn is_hostname(host: &str) -> bool {
+    parse_literal(host).is_none()
+}
+
+/// Parses an IP literal, also in brackets (`[::1]`).
+fn parse_literal(host: &str) -> Option<IpAddr> {
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    unbracketed.parse().ok()
+}
+
+/// Returns the next retry delay, doubling up to `RETRY_BACKOFF_MAX`.
+pub fn next_backoff(current: Duration) -> Duration {
+    (current * 2).min(RETRY_BACKOFF_MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_backoff() {
+        let mut delay = RETRY_BACKOFF_MIN;
+        let mut delays = Vec::new();
+        for _ in 0..8 {
+            delays.push(delay.as_secs());
+            delay = next_backoff(delay);
+        }
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(next_backoff(Duration::from_secs(45)), RETRY_BACKOFF_MAX);
+    }
+
+    #[test]
+    fn test_is_hostname() {
+        assert!(is_hostname("example.com"));
+        assert!(is_hostname("localhost"));
+        assert!(!is_hostname("192.0.2.1"));
+        assert!(!is_hostname("2001:db8::1"));
+        assert!(!is_hostname("[2001:db8::1]"));
+        assert!(is_hostname("[example.com]"));
+    }
+
+    #[tokio::test]
+    async fn test_expand_targets_keeps_literals_and_single_rows() {
+        let mut literal = Target::new("v4", "192.0.2.1".parse().unwrap());
+        literal.all_addrs = true;
+        let mut bracketed = Target::from_host("v6", "[2001:db8::1]");
+        bracketed.all_addrs = true;
+        // Not expanded, so not resolved here either
+        let single = Target::from_host("single", "example.invalid");
+
+        let expanded = expand_targets(vec![literal, bracketed, single]).await;
+        let rows: Vec<_> = expanded
+            .iter()
+            .map(|t| (t.name.as_str(), t.host.as_str(), t.parent.is_some()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("v4", "192.0.2.1", false),
+                ("v6", "[2001:db8::1]", false),
+                ("single", "example.invalid", false),
+            ]
+        );
+        assert_eq!(expanded[2].state, ResolveState::Resolving);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_all_literals() {
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        assert_eq!(resolve_all("[2001:db8::1]", None).await.unwrap(), vec![v6]);
+        assert_eq!(
+            resolve_all("192.0.2.1", None).await.unwrap(),
+            vec!["192.0.2.1".parse::<IpAddr>().unwrap()]
+        );
+    }
+}
@@ -1,18 +1,193 @@
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tdigest::TDigest;
 
 /// Maximum number of samples to keep in history.
 const MAX_HISTORY: usize = 300;
 
+/// Maximum number of address changes to remember per target.
+const MAX_ADDR_CHANGES: usize = 32;
+
+/// Default upper bounds of the latency histogram buckets (ms).
+const DEFAULT_BUCKETS_MS: [f64; 12] = [
+    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0,
+];
+
+/// A change of a target's resolved address.
+#[derive(Debug, Clone)]
+pub struct AddressChange {
+    pub from: IpAddr,
+    pub to: IpAddr,
+    /// Number of samples recorded before the change.
+    pub at_sample: u64,
+    /// When the change was observed.
+    pub at: Instant,
+}
+
+/// Sequence anomaly observed by a sequenced probe (UDP).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceEvent {
+    /// A reply arrived after a probe with a higher sequence number was sent.
+    Reordered,
+    /// A reply for an already received sequence number arrived again.
+    Duplicate,
+}
+
+/// Path MTU of a target, from Don't-Fragment probes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathMtu {
+    /// Largest packet (IP headers included) known to get through, in bytes.
+    pub mtu: usize,
+    /// Whether the search is still narrowing down.
+    pub searching: bool,
+}
+
+/// Timing breakdown of a single HTTP probe.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpPhases {
+    /// Name resolution (None for IP literal hosts).
+    pub dns: Option<Duration>,
+    /// TCP handshake.
+    pub connect: Duration,
+    /// TLS handshake (None for plain HTTP).
+    pub tls: Option<Duration>,
+    /// From sending the request to the first response byte.
+    pub ttfb: Duration,
+}
+
+/// Running averages of HTTP phase timings.
+#[derive(Debug, Default)]
+pub struct PhaseStats {
+    /// Phases of the most recent request.
+    pub last: Option<HttpPhases>,
+    dns_sum: Duration,
+    dns_count: u32,
+    connect_sum: Duration,
+    tls_sum: Duration,
+    tls_count: u32,
+    ttfb_sum: Duration,
+    count: u32,
+}
+
+impl PhaseStats {
+    pub fn record(&mut self, phases: HttpPhases) {
+        if let Some(dns) = phases.dns {
+            self.dns_sum += dns;
+            self.dns_count += 1;
+        }
+        if let Some(tls) = phases.tls {
+            self.tls_sum += tls;
+            self.tls_count += 1;
+        }
+        self.connect_sum += phases.connect;
+        self.ttfb_sum += phases.ttfb;
+        self.count += 1;
+        self.last = Some(phases);
+    }
+
+    /// Returns the average of each phase over all recorded requests.
+    pub fn average(&self) -> Option<HttpPhases> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(HttpPhases {
+            dns: (self.dns_count > 0).then(|| self.dns_sum / self.dns_count),
+            connect: self.connect_sum / self.count,
+            tls: (self.tls_count > 0).then(|| self.tls_sum / self.tls_count),
+            ttfb: self.ttfb_sum / self.count,
+        })
+    }
+}
+
+/// Why a probe failed.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// No reply within the probe timeout.
+    Timeout,
+    /// The destination host could not be reached.
+    HostUnreachable,
+    /// There is no route to the destination network.
+    NetUnreachable,
+    /// The probe's TTL / hop limit ran out on the path.
+    TtlExceeded,
+    /// The probe was larger than the path MTU and had Don't-Fragment set.
+    FragNeeded,
+    /// Not allowed to open or use the socket.
+    Permission,
+    /// The socket was recreated after repeated network errors.
+    SocketRecreated,
+    /// The target refused the connection or port.
+    Refused,
+    /// The target answered with an error (HTTP status, DNS response code).
+    BadResponse,
+    /// Any other error.
+    Other,
+}
+
+impl FailureKind {
+    /// Returns a short label for display.
+    pub fn label(self) -> &'static str {
+        match self {
+            FailureKind::Timeout => "Timeout",
+            FailureKind::HostUnreachable => "Host unreachable",
+            FailureKind::NetUnreachable => "Net unreachable",
+            FailureKind::TtlExceeded => "TTL exceeded",
+            FailureKind::FragNeeded => "Frag needed",
+            FailureKind::Permission => "Permission",
+            FailureKind::SocketRecreated => "Socket recreated",
+            FailureKind::Refused => "Refused",
+            FailureKind::BadResponse => "Bad response",
+            FailureKind::Other => "Other",
+        }
+    }
+
+    /// Classifies an I/O error.
+    pub fn from_io(err: &io::Error) -> Self {
+        #[cfg(unix)]
+        if err.raw_os_error() == Some(libc::EMSGSIZE) {
+            return FailureKind::FragNeeded;
+        }
+        match err.kind() {
+            io::ErrorKind::TimedOut => FailureKind::Timeout,
+            io::ErrorKind::HostUnreachable => FailureKind::HostUnreachable,
+            io::ErrorKind::NetworkUnreachable => FailureKind::NetUnreachable,
+            io::ErrorKind::PermissionDenied => FailureKind::Permission,
+            io::ErrorKind::ConnectionRefused => FailureKind::Refused,
+            _ => FailureKind::Other,
+        }
+    }
+}
+
 /// Ping result for a single ping attempt.
 #[derive(Debug, Clone)]
 pub enum PingResult {
     Success(Duration),
     Timeout,
-    #[allow(dead_code)]
-    Error(String),
+    /// Failure other than a timeout, with a human-readable message.
+    Error(FailureKind, String),
+}
+
+impl PingResult {
+    /// Creates an error result from an I/O error.
+    pub fn io_error(err: &io::Error) -> Self {
+        PingResult::Error(FailureKind::from_io(err), err.to_string())
+    }
+
+    /// Returns why the probe failed, or None if it succeeded.
+    pub fn failure(&self) -> Option<FailureKind> {
+        match self {
+            PingResult::Success(_) => None,
+            PingResult::Timeout => Some(FailureKind::Timeout),
+            PingResult::Error(kind, _) => Some(*kind),
+        }
+    }
 }
 
 /// All-time statistics using t-digest for streaming percentiles.
@@ -114,6 +289,126 @@ impl AllTimeStats {
     }
 }
 
+/// Upper bounds of latency histogram buckets in milliseconds, ascending.
+/// Shared by the histograms of all targets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketBounds(Arc<[f64]>);
+
+impl Default for BucketBounds {
+    fn default() -> Self {
+        Self(DEFAULT_BUCKETS_MS.into())
+    }
+}
+
+impl BucketBounds {
+    /// Validates bucket bounds.
+    pub fn new(bounds: Vec<f64>) -> Result<Self, String> {
+        if bounds.is_empty() {
+            return Err("at least one bucket bound is required".into());
+        }
+        if bounds.iter().any(|b| !b.is_finite() || *b <= 0.0) {
+            return Err("bucket bounds must be positive numbers".into());
+        }
+        if bounds.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("bucket bounds must be in ascending order".into());
+        }
+        Ok(Self(bounds.into()))
+    }
+
+    /// Parses comma-separated bounds in milliseconds, e.g. "1,5,10,50".
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let bounds = s
+            .split(',')
+            .map(|b| {
+                b.trim()
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid bucket bound \"{}\"", b.trim()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(bounds)
+    }
+
+    pub fn as_ms(&self) -> &[f64] {
+        &self.0
+    }
+}
+
+impl serde::Serialize for BucketBounds {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for BucketBounds {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bounds = Vec::<f64>::deserialize(deserializer)?;
+        Self::new(bounds).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Latency histogram with fixed buckets, in the cumulative form Prometheus
+/// uses: `counts[i]` is the number of samples at or below `bounds_ms[i]`,
+/// and `count` also includes samples above the last bound (the +Inf
+/// bucket). Histograms with the same bounds merge exactly, also across
+/// sessions.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LatencyHistogram {
+    pub bounds_ms: BucketBounds,
+    pub counts: Vec<u64>,
+    pub count: u64,
+    /// Sum of all samples in microseconds.
+    pub sum_us: u64,
+}
+
+impl LatencyHistogram {
+    pub fn new(bounds: BucketBounds) -> Self {
+        Self {
+            counts: vec![0; bounds.as_ms().len()],
+            bounds_ms: bounds,
+            count: 0,
+            sum_us: 0,
+        }
+    }
+
+    pub fn record(&mut self, d: Duration) {
+        let first = self.first_bucket(d);
+        for count in &mut self.counts[first..] {
+            *count += 1;
+        }
+        self.count += 1;
+        self.sum_us += d.as_micros() as u64;
+    }
+
+    /// Removes a previously recorded sample.
+    fn remove(&mut self, d: Duration) {
+        let first = self.first_bucket(d);
+        for count in &mut self.counts[first..] {
+            *count = count.saturating_sub(1);
+        }
+        self.count = self.count.saturating_sub(1);
+        self.sum_us = self.sum_us.saturating_sub(d.as_micros() as u64);
+    }
+
+    /// Returns the index of the smallest bucket containing `d`.
+    fn first_bucket(&self, d: Duration) -> usize {
+        let ms = d.as_secs_f64() * 1000.0;
+        self.bounds_ms.as_ms().partition_point(|&bound| bound < ms)
+    }
+
+    /// Adds the samples of another histogram with the same bounds.
+    pub fn merge(&mut self, other: &LatencyHistogram) -> Result<(), String> {
+        if self.bounds_ms != other.bounds_ms {
+            return Err("cannot merge histograms with different buckets".into());
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.sum_us += other.sum_us;
+        Ok(())
+    }
+}
+
 /// Statistics for a single target.
 #[derive(Debug)]
 pub struct TargetStats {
@@ -139,6 +434,26 @@ pub struct TargetStats {
     jitter_sum: Duration,
     /// Count for jitter calculation.
     jitter_count: u64,
+    /// Recent changes of the target's resolved address.
+    pub addr_changes: VecDeque<AddressChange>,
+    /// Replies received out of order (sequenced probes only).
+    pub reordered: u64,
+    /// Duplicate replies received (sequenced probes only).
+    pub duplicates: u64,
+    /// Phase timings (HTTP probes only).
+    pub phases: PhaseStats,
+    /// Number of failed probes by cause.
+    pub failures: BTreeMap<FailureKind, u64>,
+    /// Discovered path MTU (PMTU discovery only; kept across resets).
+    pub pmtu: Option<PathMtu>,
+    /// Whether probing is paused.
+    pub paused: bool,
+    /// Sample numbers before which probing was paused, within the history.
+    pauses: VecDeque<u64>,
+    /// All-time latency histogram.
+    pub histogram: LatencyHistogram,
+    /// Latency histogram of the samples in the history.
+    pub window_histogram: LatencyHistogram,
 }
 
 impl Default for TargetStats {
@@ -149,6 +464,11 @@ impl Default for TargetStats {
 
 impl TargetStats {
     pub fn new() -> Self {
+        Self::with_buckets(BucketBounds::default())
+    }
+
+    /// Creates stats whose latency histograms use the given buckets.
+    pub fn with_buckets(bounds: BucketBounds) -> Self {
         Self {
             history: VecDeque::with_capacity(MAX_HISTORY),
             sent: 0,
@@ -161,6 +481,16 @@ impl TargetStats {
             prev_latency: None,
             jitter_sum: Duration::ZERO,
             jitter_count: 0,
+            addr_changes: VecDeque::new(),
+            reordered: 0,
+            duplicates: 0,
+            phases: PhaseStats::default(),
+            failures: BTreeMap::new(),
+            pmtu: None,
+            paused: false,
+            pauses: VecDeque::new(),
+            histogram: LatencyHistogram::new(bounds.clone()),
+            window_histogram: LatencyHistogram::new(bounds),
         }
     }
 
@@ -168,6 +498,7 @@ impl TargetStats {
     #[allow(dead_code)]
     pub fn reset_window(&mut self) {
         self.history.clear();
+        self.window_histogram = LatencyHistogram::new(self.window_histogram.bounds_ms.clone());
     }
 
     /// Resets everything including all-time stats.
@@ -183,6 +514,17 @@ impl TargetStats {
         self.prev_latency = None;
         self.jitter_sum = Duration::ZERO;
         self.jitter_count = 0;
+        self.addr_changes.clear();
+        self.reordered = 0;
+        self.duplicates = 0;
+        self.phases = PhaseStats::default();
+        self.failures.clear();
+        self.pauses.clear();
+        self.histogram = LatencyHistogram::new(self.histogram.bounds_ms.clone());
+        self.window_histogram = LatencyHistogram::new(self.window_histogram.bounds_ms.clone());
+        if self.paused {
+            self.pauses.push_back(0);
+        }
     }
 
     /// Records a ping result.
@@ -193,6 +535,8 @@ impl TargetStats {
             PingResult::Success(d) => {
                 self.received += 1;
                 self.all_time.record(*d);
+                self.histogram.record(*d);
+                self.window_histogram.record(*d);
 
                 // Update streak
                 self.current_streak += 1;
@@ -208,17 +552,122 @@ impl TargetStats {
                 }
                 self.prev_latency = Some(*d);
             }
-            PingResult::Timeout | PingResult::Error(_) => {
+            PingResult::Timeout | PingResult::Error(..) => {
+                if let Some(kind) = result.failure() {
+                    *self.failures.entry(kind).or_insert(0) += 1;
+                }
                 self.current_streak = 0;
                 self.last_loss_at = Some(Instant::now());
                 self.prev_latency = None; // Reset jitter tracking on loss
             }
         }
 
-        if self.history.len() >= MAX_HISTORY {
-            self.history.pop_front();
+        if self.history.len() >= MAX_HISTORY
+            && let Some(PingResult::Success(d)) = self.history.pop_front()
+        {
+            self.window_histogram.remove(d);
         }
         self.history.push_back(result);
+
+        // Forget pauses before the first sample still in the history
+        let first = self.sent - self.history.len() as u64;
+        while self.pauses.front().is_some_and(|&at| at < first) {
+            self.pauses.pop_front();
+        }
+    }
+
+    /// Marks probing as paused or resumed. A pause leaves a gap in the
+    /// sparkline before the next sample.
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused && !self.paused && self.pauses.back() != Some(&self.sent) {
+            self.pauses.push_back(self.sent);
+        }
+        self.paused = paused;
+    }
+
+    /// Records a sequence anomaly reported by the prober.
+    pub fn record_sequence_event(&mut self, event: SequenceEvent) {
+        match event {
+            SequenceEvent::Reordered => self.reordered += 1,
+            SequenceEvent::Duplicate => self.duplicates += 1,
+        }
+    }
+
+    /// Records a change of the target's resolved address.
+    pub fn record_addr_change(&mut self, from: IpAddr, to: IpAddr) {
+        if self.addr_changes.len() >= MAX_ADDR_CHANGES {
+            self.addr_changes.pop_front();
+        }
+        self.addr_changes.push_back(AddressChange {
+            from,
+            to,
+            at_sample: self.sent,
+            at: Instant::now(),
+        });
+    }
+
+    /// Returns the most recent address change, if any.
+    pub fn last_addr_change(&self) -> Option<&AddressChange> {
+        self.addr_changes.back()
+    }
+
+    /// Returns the sparkline columns in order: the sample number and result
+    /// of each sample in the history, with a gap (None) where probing was
+    /// paused.
+    fn columns(&self) -> Vec<Option<(u64, &PingResult)>> {
+        let first = self.sent - self.history.len() as u64;
+        let mut pauses = self.pauses.iter().peekable();
+        let mut columns = Vec::with_capacity(self.history.len() + self.pauses.len());
+        for (i, result) in self.history.iter().enumerate() {
+            let sample = first + i as u64;
+            while pauses.next_if(|&&at| at <= sample).is_some() {
+                columns.push(None);
+            }
+            columns.push(Some((sample, result)));
+        }
+        // Still paused since the last sample
+        columns.extend(pauses.map(|_| None));
+        columns
+    }
+
+    /// Returns the last `width` sparkline columns.
+    fn last_columns(&self, width: usize) -> Vec<Option<(u64, &PingResult)>> {
+        let mut columns = self.columns();
+        columns.drain(..columns.len().saturating_sub(width));
+        columns
+    }
+
+    /// Returns sparkline columns (0-based, within the last `width` columns)
+    /// at which the target's address changed.
+    pub fn addr_change_columns(&self, width: usize) -> Vec<usize> {
+        let columns = self.last_columns(width);
+        self.addr_changes
+            .iter()
+            .filter_map(|c| {
+                columns
+                    .iter()
+                    .position(|col| col.is_some_and(|(sample, _)| sample == c.at_sample))
+            })
+            .collect()
+    }
+
+    /// Returns sparkline columns (0-based, within the last `width` columns)
+    /// that are gaps where probing was paused.
+    pub fn pause_columns(&self, width: usize) -> Vec<usize> {
+        self.last_columns(width)
+            .iter()
+            .enumerate()
+            .filter(|(_, col)| col.is_none())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Returns the message of the most recent non-timeout failure in the window.
+    pub fn last_error(&self) -> Option<&str> {
+        self.history.iter().rev().find_map(|r| match r {
+            PingResult::Error(_, msg) => Some(msg.as_str()),
+            _ => None,
+        })
     }
 
     /// Returns how long stats have been tracked.
@@ -275,18 +724,7 @@ impl TargetStats {
 
     /// Returns a quality grade based on MOS score.
     pub fn quality_grade(&self) -> Option<(&'static str, &'static str)> {
-        let mos = self.mos_score()?;
-        Some(if mos >= 4.3 {
-            ("A", "Excellent")
-        } else if mos >= 4.0 {
-            ("B", "Good")
-        } else if mos >= 3.6 {
-            ("C", "Fair")
-        } else if mos >= 3.1 {
-            ("D", "Poor")
-        } else {
-            ("F", "Bad")
-        })
+        self.mos_score().map(grade_for_mos)
     }
 
     /// Returns histogram buckets for latency distribution.
@@ -432,12 +870,12 @@ impl TargetStats {
     }
 
     /// Returns latencies as f64 milliseconds for sparkline rendering.
-    /// Timeouts/errors are represented as 0.0.
+    /// Timeouts/errors and paused gaps (see `pause_columns`) are represented as 0.0.
     pub fn sparkline_data(&self) -> Vec<u64> {
-        self.history
-            .iter()
-            .map(|r| match r {
-                PingResult::Success(d) => d.as_micros() as u64,
+        self.columns()
+            .into_iter()
+            .map(|col| match col {
+                Some((_, PingResult::Success(d))) => d.as_micros() as u64,
                 _ => 0,
             })
             .collect()
@@ -458,6 +896,97 @@ impl TargetStats {
     }
 }
 
+/// Maps a MOS score to a letter grade and description.
+fn grade_for_mos(mos: f64) -> (&'static str, &'static str) {
+    if mos >= 4.3 {
+        ("A", "Excellent")
+    } else if mos >= 4.0 {
+        ("B", "Good")
+    } else if mos >= 3.6 {
+        ("C", "Fair")
+    } else if mos >= 3.1 {
+        ("D", "Poor")
+    } else {
+        ("F", "Bad")
+    }
+}
+
+/// Returns the median of some durations (mean of the middle two for even counts).
+fn median(mut values: Vec<Duration>) -> Option<Duration> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort();
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2
+    } else {
+        values[mid]
+    })
+}
+
+/// Aggregate statistics over the targets of a group.
+#[derive(Debug, Clone, Default)]
+pub struct GroupSummary {
+    /// Number of member targets.
+    pub targets: usize,
+    /// Results in the members' recent windows.
+    pub window_count: u64,
+    /// Lost probes and loss percentage over the members' recent windows.
+    pub window_loss: (u64, f64),
+    /// Probes sent by all members.
+    pub sent: u64,
+    /// Lost probes and loss percentage over all members' probes.
+    pub all_time_loss: (u64, f64),
+    /// Median of the members' recent average latencies.
+    pub median_avg: Option<Duration>,
+    /// Median of the members' all-time average latencies.
+    pub median_all_time_avg: Option<Duration>,
+    /// Lowest MOS score among the members.
+    pub worst_mos: Option<f64>,
+}
+
+impl GroupSummary {
+    /// Aggregates the stats of a group's members.
+    pub fn new<'a>(members: impl IntoIterator<Item = &'a TargetStats>) -> Self {
+        let mut summary = Self::default();
+        let mut window_lost = 0;
+        let mut avgs = Vec::new();
+        let mut all_time_avgs = Vec::new();
+
+        for stats in members {
+            summary.targets += 1;
+            summary.window_count += stats.window_count() as u64;
+            window_lost += stats.window_packet_loss().0;
+            summary.sent += stats.sent;
+            summary.all_time_loss.0 += stats.sent - stats.received;
+            avgs.extend(stats.average());
+            all_time_avgs.extend(stats.all_time.average());
+            if let Some(mos) = stats.mos_score() {
+                summary.worst_mos = Some(summary.worst_mos.map_or(mos, |w: f64| w.min(mos)));
+            }
+        }
+
+        let pct = |lost: u64, total: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                lost as f64 / total as f64 * 100.0
+            }
+        };
+        summary.window_loss = (window_lost, pct(window_lost, summary.window_count));
+        summary.all_time_loss.1 = pct(summary.all_time_loss.0, summary.sent);
+        summary.median_avg = median(avgs);
+        summary.median_all_time_avg = median(all_time_avgs);
+        summary
+    }
+
+    /// Returns the grade of the worst member.
+    pub fn worst_grade(&self) -> Option<(&'static str, &'static str)> {
+        self.worst_mos.map(grade_for_mos)
+    }
+}
+
 /// Formats a duration as a human-readable string.
 pub fn format_duration(d: Duration) -> String {
     let micros = d.as_micros();
@@ -663,6 +1192,41 @@ mod tests {
         assert_eq!(counts.iter().sum::<u64>(), 15); // Total samples
     }
 
+    #[test]
+    fn test_latency_histogram() {
+        let bounds = BucketBounds::parse("5, 10,50").unwrap();
+        let mut stats = TargetStats::with_buckets(bounds.clone());
+        stats.record(PingResult::Success(Duration::from_millis(3)));
+        stats.record(PingResult::Success(Duration::from_millis(10)));
+        stats.record(PingResult::Timeout);
+        stats.record(PingResult::Success(Duration::from_millis(80)));
+
+        // Cumulative: samples at or below each bound, plus the +Inf total
+        assert_eq!(stats.histogram.counts, vec![1, 2, 2]);
+        assert_eq!(stats.histogram.count, 3);
+        assert_eq!(stats.histogram.sum_us, 93_000);
+        assert_eq!(stats.window_histogram, stats.histogram);
+
+        // The window histogram forgets samples leaving the history (the 3ms one)
+        for _ in 0..MAX_HISTORY - 3 {
+            stats.record(PingResult::Timeout);
+        }
+        assert_eq!(stats.window_histogram.counts, vec![0, 1, 1]);
+        assert_eq!(stats.window_histogram.count, 2);
+        assert_eq!(stats.histogram.count, 3);
+
+        let mut merged = stats.histogram.clone();
+        merged.merge(&stats.window_histogram).unwrap();
+        assert_eq!(merged.counts, vec![1, 3, 3]);
+        assert_eq!(merged.count, 5);
+        let other = LatencyHistogram::new(BucketBounds::default());
+        assert!(merged.merge(&other).is_err());
+
+        assert!(BucketBounds::parse("10,5").is_err());
+        assert!(BucketBounds::parse("0,5").is_err());
+        assert!(BucketBounds::parse("5,x").is_err());
+    }
+
     #[test]
     fn test_all_time_stats() {
         let mut stats = TargetStats::new();
@@ -748,6 +1312,58 @@ mod tests {
         assert_eq!(data[2], 20_000); // 20ms in microseconds
     }
 
+    #[test]
+    fn test_addr_change_columns() {
+        let a: IpAddr = "192.0.2.1".parse().unwrap();
+        let b: IpAddr = "192.0.2.2".parse().unwrap();
+        let mut stats = TargetStats::new();
+
+        for _ in 0..5 {
+            stats.record(PingResult::Success(Duration::from_millis(10)));
+        }
+        stats.record_addr_change(a, b);
+        for _ in 0..3 {
+            stats.record(PingResult::Success(Duration::from_millis(20)));
+        }
+
+        // Change happened before the 6th sample
+        assert_eq!(stats.addr_change_columns(100), vec![5]);
+        // Only the last 4 samples shown: change is at column 1
+        assert_eq!(stats.addr_change_columns(4), vec![1]);
+        // Change scrolled out of view
+        assert!(stats.addr_change_columns(2).is_empty());
+        assert_eq!(stats.last_addr_change().unwrap().to, b);
+    }
+
+    #[test]
+    fn test_pause_columns() {
+        let a: IpAddr = "192.0.2.1".parse().unwrap();
+        let b: IpAddr = "192.0.2.2".parse().unwrap();
+        let mut stats = TargetStats::new();
+
+        for _ in 0..3 {
+            stats.record(PingResult::Success(Duration::from_millis(10)));
+        }
+        stats.set_paused(true);
+        stats.set_paused(false);
+        stats.record_addr_change(a, b);
+        stats.record(PingResult::Success(Duration::from_millis(20)));
+
+        // The gap is a column of its own, not a lost sample
+        assert_eq!(
+            stats.sparkline_data(),
+            vec![10_000, 10_000, 10_000, 0, 20_000]
+        );
+        assert_eq!(stats.pause_columns(100), vec![3]);
+        assert_eq!(stats.addr_change_columns(100), vec![4]);
+        assert_eq!(stats.packet_loss(), 0.0);
+
+        // A pause in progress shows at the right edge
+        stats.set_paused(true);
+        assert_eq!(stats.pause_columns(3), vec![0, 2]);
+        assert_eq!(stats.pause_columns(1), vec![0]);
+    }
+
     #[test]
     fn test_packet_loss_calculation() {
         let mut stats = TargetStats::new();
@@ -761,4 +1377,59 @@ mod tests {
 
         assert!((stats.packet_loss() - 20.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_failure_breakdown() {
+        let mut stats = TargetStats::new();
+        stats.record(PingResult::Timeout);
+        stats.record(PingResult::Timeout);
+        stats.record(PingResult::Error(
+            FailureKind::HostUnreachable,
+            "No route to host".into(),
+        ));
+        stats.record(PingResult::Success(Duration::from_millis(10)));
+
+        assert_eq!(stats.failures.get(&FailureKind::Timeout), Some(&2));
+        assert_eq!(stats.failures.get(&FailureKind::HostUnreachable), Some(&1));
+        assert_eq!(
+            stats.failures.values().sum::<u64>(),
+            stats.sent - stats.received
+        );
+
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert_eq!(
+            PingResult::io_error(&refused).failure(),
+            Some(FailureKind::Refused)
+        );
+    }
+
+    #[test]
+    fn test_group_summary() {
+        let mut fast = TargetStats::new();
+        let mut medium = TargetStats::new();
+        let mut lossy = TargetStats::new();
+        for _ in 0..10 {
+            fast.record(PingResult::Success(Duration::from_millis(5)));
+            medium.record(PingResult::Success(Duration::from_millis(20)));
+        }
+        for _ in 0..5 {
+            lossy.record(PingResult::Success(Duration::from_millis(300)));
+            lossy.record(PingResult::Timeout);
+        }
+
+        let group = GroupSummary::new([&fast, &medium, &lossy]);
+        assert_eq!(group.targets, 3);
+        assert_eq!(group.sent, 30);
+        assert_eq!(group.all_time_loss.0, 5);
+        assert!((group.window_loss.1 - 100.0 / 6.0).abs() < 0.01);
+        // Median, not mean: the slow target doesn't drag it up
+        assert_eq!(group.median_avg, Some(Duration::from_millis(20)));
+        assert_eq!(group.worst_grade(), lossy.quality_grade());
+
+        assert!(GroupSummary::new([]).worst_grade().is_none());
+        assert_eq!(
+            median(vec![Duration::from_millis(10), Duration::from_millis(20)]),
+            Some(Duration::from_millis(15))
+        );
+    }
 }
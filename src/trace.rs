@@ -0,0 +1,383 @@
+use crate::ping::{PingUpdate, UpdateKind};
+use crate::stats::{FailureKind, PingResult, TargetStats};
+use anyhow::{Context, Result};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{MissedTickBehavior, interval};
+
+/// Maximum number of hops probed.
+pub const MAX_HOPS: u8 = 30;
+
+/// How long to wait for replies after sending a round of probes.
+const HOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Minimum time between probe rounds.
+const MIN_ROUND_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Payload size for hop probes.
+const PAYLOAD_SIZE: usize = 32;
+
+/// Bits of the ICMP sequence number holding the TTL (the rest is the round).
+const TTL_BITS: u16 = 5;
+
+/// Kind of ICMP message received in response to a hop probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReplyKind {
+    /// The destination answered the echo request.
+    EchoReply,
+    /// A router on the path dropped the probe when its TTL ran out.
+    TimeExceeded,
+    /// A router or the destination reported the destination unreachable.
+    Unreachable,
+}
+
+/// Result of one TTL-limited probe.
+#[derive(Debug)]
+pub struct HopReply {
+    pub ttl: u8,
+    /// Address that answered, if any.
+    pub addr: Option<IpAddr>,
+    pub result: PingResult,
+    /// Whether this hop is the end of the path.
+    pub reached: bool,
+}
+
+/// A single hop on the path to a target.
+#[derive(Debug, Default)]
+pub struct Hop {
+    /// Most recent address that answered for this hop.
+    pub addr: Option<IpAddr>,
+    pub stats: TargetStats,
+}
+
+/// A running hop discovery for one target.
+pub struct Trace {
+    /// Destination being traced.
+    pub dest: IpAddr,
+    /// Hops in TTL order (index 0 is TTL 1).
+    pub hops: Vec<Hop>,
+    /// Error that stopped the tracer, if any.
+    pub error: Option<String>,
+    handle: JoinHandle<()>,
+}
+
+impl Trace {
+    /// Starts tracing the path to `dest`, sending a round of probes every `every`.
+    pub fn start(
+        target_idx: usize,
+        dest: IpAddr,
+        every: Duration,
+        tx: mpsc::UnboundedSender<PingUpdate>,
+    ) -> Self {
+        let handle = tokio::spawn(run_tracer(target_idx, dest, every, tx));
+        Self {
+            dest,
+            hops: Vec::new(),
+            error: None,
+            handle,
+        }
+    }
+
+    /// Records the result of a hop probe.
+    pub fn record(&mut self, reply: HopReply) {
+        let i = reply.ttl.saturating_sub(1) as usize;
+        if self.hops.len() <= i {
+            // Drop probes beyond the end of the path once it is known
+            if self.hops.last().is_some_and(|h| h.addr == Some(self.dest)) {
+                return;
+            }
+            self.hops.resize_with(i + 1, Hop::default);
+        }
+
+        let hop = &mut self.hops[i];
+        if let Some(addr) = reply.addr {
+            hop.addr = Some(addr);
+        }
+        hop.stats.record(reply.result);
+
+        if reply.reached {
+            self.hops.truncate(i + 1);
+        }
+    }
+
+    /// Clears the per-hop statistics.
+    pub fn reset(&mut self) {
+        self.hops.clear();
+    }
+}
+
+impl Drop for Trace {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Opens a raw ICMP socket for the destination's address family.
+fn open_socket(dest: IpAddr) -> Result<UdpSocket> {
+    let (domain, protocol) = match dest {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    };
+    // Unprivileged ICMP sockets don't report Time Exceeded, so hop discovery needs raw sockets
+    let socket = Socket::new(domain, Type::RAW, Some(protocol))
+        .context("hop discovery needs raw ICMP sockets (run with sudo or CAP_NET_RAW)")?;
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+/// Sets the TTL / hop limit for subsequent probes.
+fn set_ttl(socket: &UdpSocket, dest: IpAddr, ttl: u8) -> std::io::Result<()> {
+    match dest {
+        IpAddr::V4(_) => socket.set_ttl(ttl as u32),
+        IpAddr::V6(_) => SockRef::from(socket).set_unicast_hops_v6(ttl as u32),
+    }
+}
+
+/// Computes the Internet checksum of an ICMPv4 message.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Builds an echo request. The kernel fills in the ICMPv6 checksum.
+fn encode_echo(dest: IpAddr, ident: u16, seq: u16) -> Vec<u8> {
+    let kind = if dest.is_ipv4() { 8 } else { 128 };
+    let mut buf = vec![0u8; 8 + PAYLOAD_SIZE];
+    buf[0] = kind;
+    buf[4..6].copy_from_slice(&ident.to_be_bytes());
+    buf[6..8].copy_from_slice(&seq.to_be_bytes());
+    if dest.is_ipv4() {
+        let sum = checksum(&buf);
+        buf[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    buf
+}
+
+/// Returns the identifier and sequence of an echo request embedded in an ICMP error.
+fn embedded_echo(icmp: &[u8], v4: bool) -> Option<(u16, u16)> {
+    let inner = icmp.get(8..)?;
+    let (header_len, request_kind) = if v4 {
+        ((inner.first()? & 0x0f) as usize * 4, 8)
+    } else {
+        (40, 128)
+    };
+    let echo = inner.get(header_len..header_len + 8)?;
+    if echo[0] != request_kind {
+        return None;
+    }
+    Some((
+        u16::from_be_bytes([echo[4], echo[5]]),
+        u16::from_be_bytes([echo[6], echo[7]]),
+    ))
+}
+
+/// Parses a received ICMPv4 packet (including its IP header) or ICMPv6 message,
+/// returning the reply kind and the identifier/sequence of the probe it answers.
+fn parse_reply(buf: &[u8], v4: bool) -> Option<(ReplyKind, u16, u16)> {
+    let icmp = if v4 {
+        buf.get((buf.first()? & 0x0f) as usize * 4..)?
+    } else {
+        buf
+    };
+    let kind = match (v4, *icmp.first()?) {
+        (true, 0) | (false, 129) => ReplyKind::EchoReply,
+        (true, 11) | (false, 3) => ReplyKind::TimeExceeded,
+        (true, 3) | (false, 1) => ReplyKind::Unreachable,
+        _ => return None,
+    };
+
+    let (ident, seq) = if kind == ReplyKind::EchoReply {
+        let header = icmp.get(..8)?;
+        (
+            u16::from_be_bytes([header[4], header[5]]),
+            u16::from_be_bytes([header[6], header[7]]),
+        )
+    } else {
+        embedded_echo(icmp, v4)?
+    };
+    Some((kind, ident, seq))
+}
+
+/// Sends rounds of TTL-limited echo requests and reports each hop's reply.
+async fn run_tracer(
+    target_idx: usize,
+    dest: IpAddr,
+    every: Duration,
+    tx: mpsc::UnboundedSender<PingUpdate>,
+) {
+    let send = |kind: UpdateKind| tx.send(PingUpdate { target_idx, kind }).is_ok();
+
+    let socket = match open_socket(dest) {
+        Ok(s) => s,
+        Err(e) => {
+            send(UpdateKind::TraceFailed(format!("{:#}", e)));
+            return;
+        }
+    };
+
+    let ident: u16 = rand::random();
+    let target = SocketAddr::new(dest, 0);
+    let mut max_ttl = MAX_HOPS;
+    let mut round: u16 = 0;
+
+    let mut tick = interval(every.max(MIN_ROUND_INTERVAL));
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tick.tick().await;
+
+        // Probe every hop at once; the sequence number carries round and TTL
+        let mut pending: HashMap<u16, (u8, Instant)> = HashMap::new();
+        for ttl in 1..=max_ttl {
+            let seq = (round << TTL_BITS) | ttl as u16;
+            let packet = encode_echo(dest, ident, seq);
+            let sent = match set_ttl(&socket, dest, ttl) {
+                Ok(()) => socket.send_to(&packet, target).await.map(|_| ()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = sent {
+                send(UpdateKind::TraceFailed(e.to_string()));
+                return;
+            }
+            pending.insert(seq, (ttl, Instant::now()));
+        }
+
+        let deadline = tokio::time::Instant::now() + HOP_TIMEOUT;
+        let mut buf = [0u8; 1500];
+        while !pending.is_empty() {
+            let (n, from) =
+                match tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+                    Ok(Ok(r)) => r,
+                    Ok(Err(e)) => {
+                        send(UpdateKind::TraceFailed(e.to_string()));
+                        return;
+                    }
+                    Err(_) => break,
+                };
+
+            // Raw sockets see all ICMP traffic; keep only replies to our probes
+            let Some((kind, reply_ident, seq)) = parse_reply(&buf[..n], dest.is_ipv4()) else {
+                continue;
+            };
+            if reply_ident != ident {
+                continue;
+            }
+            let Some((ttl, sent_at)) = pending.remove(&seq) else {
+                continue;
+            };
+
+            let reached = kind != ReplyKind::TimeExceeded;
+            if reached {
+                max_ttl = max_ttl.min(ttl);
+            }
+            let result = match kind {
+                ReplyKind::Unreachable => PingResult::Error(
+                    FailureKind::HostUnreachable,
+                    "Destination unreachable".into(),
+                ),
+                _ => PingResult::Success(sent_at.elapsed()),
+            };
+            let reply = HopReply {
+                ttl,
+                addr: Some(from.ip()),
+                result,
+                reached,
+            };
+            if !send(UpdateKind::Hop(reply)) {
+                return;
+            }
+        }
+
+        // Hops that didn't answer in time count as lost
+        let mut lost: Vec<u8> = pending.into_values().map(|(ttl, _)| ttl).collect();
+        lost.sort_unstable();
+        for ttl in lost.into_iter().filter(|t| *t <= max_ttl) {
+            let reply = HopReply {
+                ttl,
+                addr: None,
+                result: PingResult::Timeout,
+                reached: false,
+            };
+            if !send(UpdateKind::Hop(reply)) {
+                return;
+            }
+        }
+
+        round = (round + 1) & (u16::MAX >> TTL_BITS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_exceeded_v4() {
+        let probe = encode_echo("192.0.2.1".parse().unwrap(), 0xabcd, 7);
+        assert_eq!(checksum(&probe), 0);
+
+        // Outer IPv4 header, Time Exceeded, inner IPv4 header, original echo request
+        let mut packet = vec![0x45; 1];
+        packet.resize(20, 0);
+        packet.extend_from_slice(&[11, 0, 0, 0, 0, 0, 0, 0]);
+        packet.push(0x45);
+        packet.resize(48, 0);
+        packet.extend_from_slice(&probe[..8]);
+
+        assert_eq!(
+            parse_reply(&packet, true),
+            Some((ReplyKind::TimeExceeded, 0xabcd, 7))
+        );
+    }
+
+    #[test]
+    fn test_parse_echo_reply_v6() {
+        let mut packet = encode_echo("2001:db8::1".parse().unwrap(), 0x1234, 99);
+        packet[0] = 129;
+        assert_eq!(
+            parse_reply(&packet, false),
+            Some((ReplyKind::EchoReply, 0x1234, 99))
+        );
+
+        // Our own echo request is not a reply
+        packet[0] = 128;
+        assert_eq!(parse_reply(&packet, false), None);
+    }
+
+    #[tokio::test]
+    async fn test_trace_record_truncates_at_destination() {
+        let dest: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut trace = Trace {
+            dest,
+            hops: Vec::new(),
+            error: None,
+            handle: tokio::spawn(async {}),
+        };
+
+        let hop = |ttl: u8, addr: Option<IpAddr>, reached: bool| HopReply {
+            ttl,
+            addr,
+            result: PingResult::Success(Duration::from_millis(ttl as u64)),
+            reached,
+        };
+        trace.record(hop(1, Some("10.0.0.1".parse().unwrap()), false));
+        trace.record(hop(5, None, false));
+        trace.record(hop(3, Some(dest), true));
+        trace.record(hop(4, None, false));
+
+        assert_eq!(trace.hops.len(), 3);
+        assert_eq!(trace.hops[2].addr, Some(dest));
+        assert_eq!(trace.hops[1].stats.sent, 0);
+    }
+}
@@ -1,18 +1,27 @@
-use crate::app::{App, ViewMode};
-use crate::config::Target;
+use crate::app::{App, ListRow, Prompt, ViewMode};
+use crate::config::{ResolveState, Target};
+use crate::probe::{HttpTarget, ProbeKind};
 use crate::replay::ReplayState;
-use crate::stats::{TargetStats, format_duration_opt, format_elapsed};
+use crate::stats::{
+    FailureKind, GroupSummary, HttpPhases, TargetStats, format_duration, format_duration_opt,
+    format_elapsed,
+};
+use crate::trace::{MAX_HOPS, Trace};
 use chrono::Local;
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Cell, Gauge, Paragraph, Row, Sparkline, Table},
+    widgets::{
+        Block, Borders, Cell, Gauge, Paragraph, Row, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Sparkline, Table,
+    },
 };
+use std::time::Duration;
 
 /// Renders the entire UI.
-pub fn render(frame: &mut Frame, app: &App) {
+pub fn render(frame: &mut Frame, app: &mut App) {
     match app.view_mode {
         ViewMode::List => render_list_view(frame, app),
         ViewMode::Detail => render_detail_view(frame, app),
@@ -20,7 +29,7 @@ pub fn render(frame: &mut Frame, app: &App) {
 }
 
 /// Renders the list view (main view).
-fn render_list_view(frame: &mut Frame, app: &App) {
+fn render_list_view(frame: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
@@ -31,9 +40,55 @@ fn render_list_view(frame: &mut Frame, app: &App) {
         ])
         .split(frame.area());
 
+    app.update_viewport(table_page_size(chunks[1], app.compact));
     render_header(frame, chunks[0], None, app);
     render_table(frame, chunks[1], app);
-    render_footer(frame, chunks[2], ViewMode::List);
+    match &app.prompt {
+        Some(prompt) => render_prompt(frame, chunks[2], prompt, app),
+        None => render_footer(frame, chunks[2], ViewMode::List),
+    }
+}
+
+/// Renders the open prompt in place of the footer.
+fn render_prompt(frame: &mut Frame, area: Rect, prompt: &Prompt, app: &App) {
+    let key = Style::default().fg(Color::Yellow);
+    let hint = Style::default().fg(Color::DarkGray);
+    let cursor = Span::styled("█", Style::default().fg(Color::Gray));
+    let spans = match prompt {
+        Prompt::Filter => vec![
+            Span::styled("/", key),
+            Span::raw(app.filter.clone()),
+            cursor,
+            Span::styled("  Enter apply  Esc clear", hint),
+        ],
+        Prompt::AddTarget { input, error } => {
+            let mut spans = vec![
+                Span::styled("Add target: ", key),
+                Span::raw(input.clone()),
+                cursor,
+            ];
+            match error {
+                Some(e) => spans.push(Span::styled(
+                    format!("  {}", e),
+                    Style::default().fg(Color::Red),
+                )),
+                None => spans.push(Span::styled(
+                    "  host, IP or tcp:// udp:// http(s):// dns:// URL  Enter add  Esc cancel",
+                    hint,
+                )),
+            }
+            spans
+        }
+        Prompt::RemoveTarget(idx) => vec![
+            Span::styled(
+                format!("Remove {}? ", app.targets[*idx].display_name()),
+                key,
+            ),
+            Span::raw("y/n"),
+        ],
+    };
+    let paragraph = Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::TOP));
+    frame.render_widget(paragraph, area);
 }
 
 /// Formats session duration for display.
@@ -106,49 +161,95 @@ fn render_header(frame: &mut Frame, area: Rect, subtitle: Option<&str>, app: &Ap
         spans.push(Span::styled("●REC", Style::default().fg(Color::Red)));
     }
 
+    if app.all_paused() {
+        spans.push(Span::raw(" │ "));
+        spans.push(Span::styled("⏸ PAUSED", Style::default().fg(Color::Yellow)));
+    }
+
     let header = Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::BOTTOM));
 
     frame.render_widget(header, area);
 }
 
-/// Renders the main target table.
+/// Width of the columns left of the history sparkline.
+const STATS_COLUMNS_WIDTH: u16 = 88;
+
+/// Returns how many rows fit in a table of the given area.
+fn table_page_size(area: Rect, compact: bool) -> usize {
+    let lines_per_row = if compact { 1 } else { 2 };
+    let table_inner = Block::default().borders(Borders::ALL).inner(area);
+    // One line for the header
+    (table_inner.height.saturating_sub(1) / lines_per_row).max(1) as usize
+}
+
+/// Renders the main target table, showing the rows in the app's viewport.
 fn render_table(frame: &mut Frame, area: Rect, app: &App) {
+    let sort_arrow = if app.sort_descending { "▼" } else { "▲" };
     let header_cells = [
         "Target", "n", "Avg", "Min", "Max", "P50", "P95", "Loss", "History",
     ]
     .iter()
-    .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow)));
+    .map(|h| {
+        let label = match app.sort {
+            Some(key) if key.label() == *h => format!("{} {}", h, sort_arrow),
+            _ => h.to_string(),
+        };
+        Cell::from(label).style(Style::default().fg(Color::Yellow))
+    });
     let header = Row::new(header_cells).height(1);
 
-    // Calculate row height based on available space
-    let table_inner = Block::default().borders(Borders::ALL).inner(area);
-    let header_height = 1u16;
-    let num_targets = app.targets.len() as u16;
-    let available_height = table_inner.height.saturating_sub(header_height);
-    // Each target has 2 rows, so divide by 2 * num_targets
-    let row_height = if num_targets > 0 {
-        (available_height / (num_targets * 2)).max(1)
-    } else {
-        1
-    };
+    let list_rows = app.rows();
+    let visible =
+        app.scroll.min(list_rows.len())..(app.scroll + app.page_size).min(list_rows.len());
+    let row_height = table_row_height(area, app, list_rows.len());
 
-    let rows: Vec<Row> = app
-        .targets
+    let rows: Vec<Row> = list_rows[visible.clone()]
         .iter()
-        .zip(app.stats.iter())
-        .enumerate()
-        .flat_map(|(idx, (target, stats))| {
-            let is_selected = idx == app.selected;
-            create_target_rows(
-                target.name.as_str(),
-                &target.addr.to_string(),
-                stats,
-                is_selected,
-                row_height,
-            )
+        .flat_map(|row| {
+            let mut rows = match row {
+                ListRow::Group(group) => {
+                    let is_selected = app.selected_group.as_ref() == Some(group);
+                    let members = app
+                        .targets
+                        .iter()
+                        .zip(&app.stats)
+                        .filter(|(t, _)| t.active && t.group.as_ref() == Some(group))
+                        .map(|(_, s)| s);
+                    let summary = GroupSummary::new(members);
+                    let collapsed = app.collapsed.contains(group);
+                    create_group_rows(group, &summary, collapsed, is_selected, row_height)
+                }
+                ListRow::Target(idx) => {
+                    let is_selected = app.selected_group.is_none() && *idx == app.selected;
+                    let (target, stats) = (&app.targets[*idx], &app.stats[*idx]);
+                    create_target_rows(target, stats, is_selected, row_height)
+                }
+            };
+            if app.compact {
+                rows.truncate(1);
+            }
+            rows
         })
         .collect();
 
+    // Position indicator once the rows no longer fit
+    let mut title = if list_rows.len() > app.page_size {
+        format!(
+            "Targets {}-{} of {}",
+            visible.start + 1,
+            visible.end,
+            list_rows.len()
+        )
+    } else {
+        "Targets".to_string()
+    };
+    if let Some(key) = app.sort {
+        title.push_str(&format!(" · sorted by {} {}", key.label(), sort_arrow));
+    }
+    if !app.filter.is_empty() {
+        title.push_str(&format!(" · filter \"{}\"", app.filter));
+    }
+
     let table = Table::new(
         rows,
         [
@@ -164,58 +265,175 @@ fn render_table(frame: &mut Frame, area: Rect, app: &App) {
         ],
     )
     .header(header)
-    .block(Block::default().borders(Borders::ALL).title("Targets"));
+    .block(Block::default().borders(Borders::ALL).title(title));
 
     frame.render_widget(table, area);
 
     // Render sparklines in a second pass
-    render_sparklines(frame, area, app);
+    render_sparklines(frame, area, app, &list_rows[visible], row_height);
+
+    if list_rows.len() > app.page_size {
+        let mut state =
+            ScrollbarState::new(list_rows.len().saturating_sub(app.page_size)).position(app.scroll);
+        let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None);
+        frame.render_stateful_widget(
+            scrollbar,
+            area.inner(Margin {
+                vertical: 1,
+                horizontal: 0,
+            }),
+            &mut state,
+        );
+    }
 }
 
-/// Creates two table rows for a target: window stats and all-time stats.
-fn create_target_rows<'a>(
-    name: &str,
-    addr: &str,
-    stats: &TargetStats,
-    selected: bool,
-    row_height: u16,
-) -> Vec<Row<'a>> {
-    let (base_style, dim_color) = if selected {
+/// Returns the height of each table row: rows share the space while they
+/// all fit, otherwise every row is one line high.
+fn table_row_height(area: Rect, app: &App, num_rows: usize) -> u16 {
+    let lines_per_row = if app.compact { 1 } else { 2 };
+    let table_inner = Block::default().borders(Borders::ALL).inner(area);
+    let available_height = table_inner.height.saturating_sub(1);
+    if num_rows == 0 || num_rows > app.page_size {
+        1
+    } else {
+        (available_height / (num_rows as u16 * lines_per_row)).max(1)
+    }
+}
+
+/// Returns the color for a packet loss percentage.
+fn loss_color(loss: f64) -> Color {
+    if loss > 10.0 {
+        Color::Red
+    } else if loss > 1.0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+/// Returns the color for a quality grade.
+fn grade_color(grade: &str) -> Color {
+    match grade {
+        "A" => Color::Green,
+        "B" => Color::LightGreen,
+        "C" => Color::Yellow,
+        "D" => Color::LightRed,
+        _ => Color::Red,
+    }
+}
+
+/// Formats packet loss as "count (pct%)".
+fn format_loss(lost: u64, loss_pct: f64) -> String {
+    if loss_pct == 0.0 {
+        format!("{} (0%)", lost)
+    } else if loss_pct < 1.0 {
+        format!("{} ({:.2}%)", lost, loss_pct)
+    } else {
+        format!("{} ({:.1}%)", lost, loss_pct)
+    }
+}
+
+/// Returns the base style and dimmed text color of a table row.
+fn row_styles(selected: bool) -> (Style, Color) {
+    if selected {
         (
             Style::default().bg(Color::Indexed(236)),
             Color::Indexed(245),
         ) // Subtle dark bg, lighter gray text
     } else {
         (Style::default(), Color::DarkGray)
-    };
+    }
+}
 
-    let loss_color = |loss: f64| {
-        if loss > 10.0 {
-            Color::Red
-        } else if loss > 1.0 {
-            Color::Yellow
-        } else {
-            Color::Green
-        }
+/// Creates two header rows for a group: aggregate window stats (with the
+/// worst member's grade) and aggregate all-time stats.
+fn create_group_rows<'a>(
+    group: &str,
+    summary: &GroupSummary,
+    collapsed: bool,
+    selected: bool,
+    row_height: u16,
+) -> Vec<Row<'a>> {
+    let (base_style, dim_color) = row_styles(selected);
+    let marker = if collapsed { "▸" } else { "▾" };
+    let (grade, grade_color) = match summary.worst_grade() {
+        Some((grade, _)) => (format!("grade {}", grade), grade_color(grade)),
+        None => ("-".to_string(), dim_color),
     };
 
-    // Format packet loss as "count (pct%)"
-    let format_loss = |lost: u64, loss_pct: f64| -> String {
-        if loss_pct == 0.0 {
-            format!("{} (0%)", lost)
-        } else if loss_pct < 1.0 {
-            format!("{} ({:.2}%)", lost, loss_pct)
-        } else {
-            format!("{} ({:.1}%)", lost, loss_pct)
-        }
-    };
+    // Row 1: Window stats across members
+    let window_row = Row::new(vec![
+        Cell::from(format!("{} {} ({})", marker, group, summary.targets)).style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Cell::from(grade).style(Style::default().fg(grade_color)),
+        Cell::from(format_duration_opt(summary.median_avg)),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(format_loss(summary.window_loss.0, summary.window_loss.1))
+            .style(Style::default().fg(loss_color(summary.window_loss.1))),
+        Cell::from(""),
+    ])
+    .style(base_style)
+    .height(row_height);
+
+    // Row 2: All-time stats across members
+    let dim = Style::default().fg(dim_color);
+    let all_time_row = Row::new(vec![
+        Cell::from(format!("└ {} targets, median avg", summary.targets)).style(dim),
+        Cell::from(format!("all {}", format_count(summary.sent))).style(dim),
+        Cell::from(format_duration_opt(summary.median_all_time_avg)).style(dim),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(format_loss(
+            summary.all_time_loss.0,
+            summary.all_time_loss.1,
+        ))
+        .style(
+            Style::default()
+                .fg(loss_color(summary.all_time_loss.1))
+                .add_modifier(Modifier::DIM),
+        ),
+        Cell::from(""),
+    ])
+    .style(base_style)
+    .height(row_height);
+
+    vec![window_row, all_time_row]
+}
+
+/// Creates two table rows for a target: window stats and all-time stats.
+fn create_target_rows<'a>(
+    target: &Target,
+    stats: &TargetStats,
+    selected: bool,
+    row_height: u16,
+) -> Vec<Row<'a>> {
+    let (base_style, dim_color) = row_styles(selected);
 
     let (window_lost, window_loss_pct) = stats.window_packet_loss();
     let (all_time_lost, all_time_loss_pct) = stats.all_time_packet_loss();
 
     // Row 1: Window stats (recent)
+    let name = if stats.paused {
+        Cell::from(format!("⏸ {}", target.display_name())).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else {
+        Cell::from(target.display_name()).style(Style::default().add_modifier(Modifier::BOLD))
+    };
     let window_row = Row::new(vec![
-        Cell::from(name.to_string()).style(Style::default().add_modifier(Modifier::BOLD)),
+        name,
         Cell::from(format!(
             "last {}",
             format_count(stats.window_count() as u64)
@@ -235,8 +453,13 @@ fn create_target_rows<'a>(
     // Row 2: All-time stats
     let all_time = &stats.all_time;
     let dim = Style::default().fg(dim_color);
+    let addr_style = match target.state {
+        ResolveState::Resolved(_) => dim,
+        ResolveState::Resolving => Style::default().fg(Color::Yellow),
+        ResolveState::Unresolved(_) => Style::default().fg(Color::Red),
+    };
     let all_time_row = Row::new(vec![
-        Cell::from(format!("└ {}", addr)).style(dim),
+        Cell::from(format!("└ {}", target.addr_label())).style(addr_style),
         Cell::from(format!("all {}", format_count(stats.sent))).style(dim),
         Cell::from(format_duration_opt(all_time.average())).style(dim),
         Cell::from(format_duration_opt(all_time.min)).style(dim),
@@ -256,39 +479,37 @@ fn create_target_rows<'a>(
     vec![window_row, all_time_row]
 }
 
-/// Renders sparklines for each target.
-fn render_sparklines(frame: &mut Frame, area: Rect, app: &App) {
+/// Renders sparklines for the targets among the visible rows.
+fn render_sparklines(frame: &mut Frame, area: Rect, app: &App, rows: &[ListRow], row_height: u16) {
     let table_inner = Block::default().borders(Borders::ALL).inner(area);
 
     let header_height = 1u16;
-    let num_targets = app.stats.len() as u16;
-
-    // Calculate row height to match table layout (must be consistent with render_table)
-    let available_height = table_inner.height.saturating_sub(header_height);
-    let row_height = if num_targets > 0 {
-        (available_height / (num_targets * 2)).max(1)
-    } else {
-        1
-    };
-    let rows_per_target = row_height * 2;
+    // Must be consistent with render_table
+    let lines_per_row = row_height * if app.compact { 1 } else { 2 };
 
-    for (idx, stats) in app.stats.iter().enumerate() {
-        // Sparkline goes on the first row of each target pair
-        let y = table_inner.y + header_height + (idx as u16 * rows_per_target);
+    for (row, list_row) in rows.iter().enumerate() {
+        let ListRow::Target(idx) = list_row else {
+            continue;
+        };
+        let stats = &app.stats[*idx];
+        // Sparkline goes on the first line of each target
+        let y = table_inner.y + header_height + (row as u16 * lines_per_row);
         if y >= table_inner.y + table_inner.height {
             break;
         }
 
         // Sparkline column starts after the other columns
-        // Width: 26 + 8 + 8 + 8 + 8 + 8 + 8 + 14 = 88
         // Add offset to avoid rendering artifacts on bottom rows
         let sparkline_offset = 8u16;
-        let x = table_inner.x + 88 + sparkline_offset;
-        let width = table_inner.width.saturating_sub(88 + sparkline_offset);
+        let x = table_inner.x + STATS_COLUMNS_WIDTH + sparkline_offset;
+        // Leave the right edge for the scrollbar
+        let width = table_inner
+            .width
+            .saturating_sub(STATS_COLUMNS_WIDTH + sparkline_offset + 1);
 
         if width > 0 {
-            // Sparkline spans available rows for this target
-            let sparkline_height = rows_per_target.min(table_inner.y + table_inner.height - y);
+            // Sparkline spans available lines for this target
+            let sparkline_height = lines_per_row.min(table_inner.y + table_inner.height - y);
             let sparkline_area = Rect::new(x, y, width, sparkline_height);
             let data = stats.sparkline_data();
 
@@ -306,10 +527,22 @@ fn render_sparklines(frame: &mut Frame, area: Rect, app: &App) {
                 .style(Style::default().fg(Color::Cyan));
 
             frame.render_widget(sparkline, sparkline_area);
+            render_pause_gaps(frame, sparkline_area, stats);
         }
     }
 }
 
+/// Marks the columns of a sparkline where probing was paused, so that gaps
+/// don't read as loss.
+fn render_pause_gaps(frame: &mut Frame, area: Rect, stats: &TargetStats) {
+    for col in stats.pause_columns(area.width as usize) {
+        let gap = Rect::new(area.x + col as u16, area.y, 1, area.height);
+        let marker = Paragraph::new(vec![Line::from("┆"); area.height as usize])
+            .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(marker, gap);
+    }
+}
+
 /// Renders the footer with help text.
 fn render_footer(frame: &mut Frame, area: Rect, mode: ViewMode) {
     let spans = match mode {
@@ -318,8 +551,24 @@ fn render_footer(frame: &mut Frame, area: Rect, mode: ViewMode) {
             Span::raw(" quit  "),
             Span::styled("↑/↓", Style::default().fg(Color::Yellow)),
             Span::raw(" navigate  "),
+            Span::styled("PgUp/PgDn", Style::default().fg(Color::Yellow)),
+            Span::raw(" page  "),
             Span::styled("Enter", Style::default().fg(Color::Yellow)),
             Span::raw(" details  "),
+            Span::styled("Space", Style::default().fg(Color::Yellow)),
+            Span::raw(" fold group  "),
+            Span::styled("s/S", Style::default().fg(Color::Yellow)),
+            Span::raw(" sort/reverse  "),
+            Span::styled("/", Style::default().fg(Color::Yellow)),
+            Span::raw(" filter  "),
+            Span::styled("a/d", Style::default().fg(Color::Yellow)),
+            Span::raw(" add/remove  "),
+            Span::styled("p/P", Style::default().fg(Color::Yellow)),
+            Span::raw(" pause/all  "),
+            Span::styled("+/- </>", Style::default().fg(Color::Yellow)),
+            Span::raw(" interval/all  "),
+            Span::styled("c", Style::default().fg(Color::Yellow)),
+            Span::raw(" compact  "),
             Span::styled("r", Style::default().fg(Color::Yellow)),
             Span::raw(" reset"),
         ],
@@ -331,7 +580,13 @@ fn render_footer(frame: &mut Frame, area: Rect, mode: ViewMode) {
             Span::styled("q", Style::default().fg(Color::Yellow)),
             Span::raw(" quit  "),
             Span::styled("r", Style::default().fg(Color::Yellow)),
-            Span::raw(" reset"),
+            Span::raw(" reset  "),
+            Span::styled("p", Style::default().fg(Color::Yellow)),
+            Span::raw(" pause  "),
+            Span::styled("+/-", Style::default().fg(Color::Yellow)),
+            Span::raw(" interval  "),
+            Span::styled("t", Style::default().fg(Color::Yellow)),
+            Span::raw(" trace hops"),
         ],
     };
 
@@ -347,26 +602,152 @@ fn render_detail_view(frame: &mut Frame, app: &App) {
         None => return,
     };
 
+    // HTTP targets get an extra row with the request phase breakdown
+    let phase_rows = if matches!(target.probe, ProbeKind::Http(_)) {
+        4
+    } else {
+        0
+    };
+
+    // While tracing, the hop table takes the place of the histogram
+    let trace = app.selected_trace();
+    let middle_rows = match trace {
+        Some(trace) => (trace.hops.len().max(1) + 3).min(MAX_HOPS as usize + 3) as u16,
+        None => 6,
+    };
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
-            Constraint::Length(3), // Header
-            Constraint::Length(8), // Top section: Quality + Percentiles
-            Constraint::Length(6), // Histogram
-            Constraint::Min(6),    // Large sparkline
-            Constraint::Length(5), // Packet loss details
-            Constraint::Length(3), // Footer
+            Constraint::Length(3),           // Header
+            Constraint::Length(8),           // Top section: Quality + Percentiles
+            Constraint::Length(middle_rows), // Histogram or hops
+            Constraint::Min(6),              // Large sparkline
+            Constraint::Length(phase_rows),  // HTTP timing (HTTP probes only)
+            Constraint::Length(5),           // Packet loss details
+            Constraint::Length(3),           // Footer
         ])
         .split(frame.area());
 
-    let subtitle = format!("{} ({})", target.name, target.addr);
+    let subtitle = match &target.state {
+        ResolveState::Unresolved(err) => {
+            format!("{} (unresolved: {})", target.display_name(), err)
+        }
+        _ => format!("{} ({})", target.display_name(), target.addr_label()),
+    };
+    let every = format_duration(app.target_interval(app.selected));
+    let schedule = if stats.paused {
+        format!("every {} (paused)", every)
+    } else {
+        format!("every {}", every)
+    };
+    let subtitle = format!("{} · {} · {}", subtitle, schedule, target.options.summary());
     render_header(frame, chunks[0], Some(&subtitle), app);
     render_detail_top(frame, chunks[1], stats);
-    render_histogram(frame, chunks[2], stats);
+    match trace {
+        Some(trace) => render_hops(frame, chunks[2], trace),
+        None => render_histogram(frame, chunks[2], stats),
+    }
     render_large_sparkline(frame, chunks[3], stats);
-    render_loss_details(frame, chunks[4], stats);
-    render_footer(frame, chunks[5], ViewMode::Detail);
+    if let ProbeKind::Http(http) = &target.probe {
+        render_http_phases(frame, chunks[4], http, stats);
+    }
+    render_loss_details(frame, chunks[5], target, stats);
+    render_footer(frame, chunks[6], ViewMode::Detail);
+}
+
+/// Renders per-hop loss and latency of a running hop discovery.
+fn render_hops(frame: &mut Frame, area: Rect, trace: &Trace) {
+    let title = format!("Hops to {} (t to stop)", trace.dest);
+    let block = Block::default().borders(Borders::ALL).title(title);
+
+    if let Some(err) = &trace.error {
+        let widget =
+            Paragraph::new(Span::styled(err.clone(), Style::default().fg(Color::Red))).block(block);
+        frame.render_widget(widget, area);
+        return;
+    }
+    if trace.hops.is_empty() {
+        let widget = Paragraph::new(Span::styled(
+            "discovering…",
+            Style::default().fg(Color::DarkGray),
+        ))
+        .block(block);
+        frame.render_widget(widget, area);
+        return;
+    }
+
+    let header = Row::new(vec![
+        "#", "Host", "Loss", "Sent", "Last", "Avg", "Best", "Worst", "Jitter",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows = trace.hops.iter().enumerate().map(|(i, hop)| {
+        let stats = &hop.stats;
+        let loss = stats.packet_loss();
+        let host = match hop.addr {
+            Some(addr) => Cell::from(addr.to_string()),
+            None => Cell::from("???").style(Style::default().fg(Color::DarkGray)),
+        };
+        Row::new(vec![
+            Cell::from(format!("{}", i + 1)),
+            host,
+            Cell::from(format!("{:.1}%", loss)).style(Style::default().fg(loss_color(loss))),
+            Cell::from(format!("{}", stats.sent)),
+            Cell::from(format_duration_opt(stats.current())),
+            Cell::from(format_duration_opt(stats.all_time.average())),
+            Cell::from(format_duration_opt(stats.all_time.min)),
+            Cell::from(format_duration_opt(stats.all_time.max)),
+            Cell::from(format_duration_opt(stats.jitter())),
+        ])
+    });
+
+    let widths = [
+        Constraint::Length(3),
+        Constraint::Min(16),
+        Constraint::Length(7),
+        Constraint::Length(6),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(9),
+    ];
+    let table = Table::new(rows, widths).header(header).block(block);
+    frame.render_widget(table, area);
+}
+
+/// Renders the DNS/connect/TLS/TTFB breakdown of HTTP probes.
+fn render_http_phases(frame: &mut Frame, area: Rect, http: &HttpTarget, stats: &TargetStats) {
+    let phase_line = |label: &'static str, phases: Option<HttpPhases>| {
+        let value = |d: Option<Duration>| {
+            Span::styled(format_duration_opt(d), Style::default().fg(Color::Cyan))
+        };
+        Line::from(vec![
+            Span::styled(
+                format!("{:<5}", label),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" DNS: "),
+            value(phases.and_then(|p| p.dns)),
+            Span::raw("  Connect: "),
+            value(phases.map(|p| p.connect)),
+            Span::raw("  TLS: "),
+            value(phases.and_then(|p| p.tls)),
+            Span::raw("  TTFB: "),
+            value(phases.map(|p| p.ttfb)),
+        ])
+    };
+
+    let text = vec![
+        phase_line("Last", stats.phases.last),
+        phase_line("Avg", stats.phases.average()),
+    ];
+
+    let title = format!("HTTP Timing (expect {})", http.expect);
+    let widget = Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(widget, area);
 }
 
 /// Renders the top section with quality score and percentiles.
@@ -384,15 +765,9 @@ fn render_detail_top(frame: &mut Frame, area: Rect, stats: &TargetStats) {
         .unwrap_or("-".to_string());
     let jitter = format_duration_opt(stats.jitter());
 
-    let grade_color = match grade {
-        "A" => Color::Green,
-        "B" => Color::LightGreen,
-        "C" => Color::Yellow,
-        "D" => Color::LightRed,
-        _ => Color::Red,
-    };
+    let grade_color = grade_color(grade);
 
-    let quality_text = vec![
+    let mut quality_text = vec![
         Line::from(vec![
             Span::raw("Quality: "),
             Span::styled(
@@ -422,6 +797,17 @@ fn render_detail_top(frame: &mut Frame, area: Rect, stats: &TargetStats) {
             ),
         ]),
     ];
+    if let Some(pmtu) = stats.pmtu {
+        let (label, color) = if pmtu.searching {
+            (format!("≥ {} B (searching)", pmtu.mtu), Color::Yellow)
+        } else {
+            (format!("{} B", pmtu.mtu), Color::Cyan)
+        };
+        quality_text.push(Line::from(vec![
+            Span::raw("Path MTU: "),
+            Span::styled(label, Style::default().fg(color)),
+        ]));
+    }
 
     let quality_widget =
         Paragraph::new(quality_text).block(Block::default().borders(Borders::ALL).title("Quality"));
@@ -535,10 +921,27 @@ fn render_histogram(frame: &mut Frame, area: Rect, stats: &TargetStats) {
 }
 
 /// Renders a large sparkline for the detail view.
+///
+/// Address changes are marked with ▼ on the top border above the sample
+/// where the new address took effect.
 fn render_large_sparkline(frame: &mut Frame, area: Rect, stats: &TargetStats) {
+    let mut title = vec![Span::raw("Recent History")];
+    if let Some(change) = stats.last_addr_change() {
+        title.push(Span::raw(" │ "));
+        title.push(Span::styled(
+            format!(
+                "▼ address changed {} ago: {} → {}",
+                format_elapsed(change.at.elapsed()),
+                change.from,
+                change.to
+            ),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+
     let block = Block::default()
         .borders(Borders::ALL)
-        .title("Recent History");
+        .title(Line::from(title));
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
@@ -556,10 +959,17 @@ fn render_large_sparkline(frame: &mut Frame, area: Rect, stats: &TargetStats) {
         .style(Style::default().fg(Color::Cyan));
 
     frame.render_widget(sparkline, inner);
+    render_pause_gaps(frame, inner, stats);
+
+    for col in stats.addr_change_columns(inner.width as usize) {
+        let marker_area = Rect::new(inner.x + col as u16, area.y, 1, 1);
+        let marker = Paragraph::new("▼").style(Style::default().fg(Color::Magenta));
+        frame.render_widget(marker, marker_area);
+    }
 }
 
 /// Renders packet loss details.
-fn render_loss_details(frame: &mut Frame, area: Rect, stats: &TargetStats) {
+fn render_loss_details(frame: &mut Frame, area: Rect, target: &Target, stats: &TargetStats) {
     let lost = stats.sent - stats.received;
     let loss_pct = stats.packet_loss();
 
@@ -576,7 +986,34 @@ fn render_loss_details(frame: &mut Frame, area: Rect, stats: &TargetStats) {
         Color::Green
     };
 
-    let loss_text = vec![
+    let mut last_line = vec![
+        Span::raw("Last Loss: "),
+        Span::styled(
+            format!("{} ago", time_since_loss),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ];
+
+    // Sequenced probes also report late and duplicate replies
+    if matches!(target.probe, ProbeKind::Udp { .. }) {
+        let anomaly_color = |n: u64| if n > 0 { Color::Yellow } else { Color::Green };
+        last_line.extend([
+            Span::raw("  │  "),
+            Span::raw("Reordered: "),
+            Span::styled(
+                format!("{}", stats.reordered),
+                Style::default().fg(anomaly_color(stats.reordered)),
+            ),
+            Span::raw("  │  "),
+            Span::raw("Duplicates: "),
+            Span::styled(
+                format!("{}", stats.duplicates),
+                Style::default().fg(anomaly_color(stats.duplicates)),
+            ),
+        ]);
+    }
+
+    let mut loss_text = vec![
         Line::from(vec![
             Span::raw("Total Lost: "),
             Span::styled(
@@ -596,15 +1033,36 @@ fn render_loss_details(frame: &mut Frame, area: Rect, stats: &TargetStats) {
                 Style::default().fg(Color::Cyan),
             ),
         ]),
-        Line::from(vec![
-            Span::raw("Last Loss: "),
-            Span::styled(
-                format!("{} ago", time_since_loss),
-                Style::default().fg(Color::DarkGray),
-            ),
-        ]),
+        Line::from(last_line),
     ];
 
+    // Breakdown of failures by cause
+    if !stats.failures.is_empty() {
+        let mut causes = vec![Span::raw("Causes: ")];
+        for (i, (kind, count)) in stats.failures.iter().enumerate() {
+            if i > 0 {
+                causes.push(Span::raw(" · "));
+            }
+            let color = if *kind == FailureKind::Timeout {
+                Color::Yellow
+            } else {
+                Color::Red
+            };
+            causes.push(Span::styled(
+                format!("{} {}", kind.label(), count),
+                Style::default().fg(color),
+            ));
+        }
+        if let Some(err) = stats.last_error() {
+            causes.push(Span::raw("  │  "));
+            causes.push(Span::styled(
+                format!("Last error: {}", err),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        loss_text.push(Line::from(causes));
+    }
+
     let loss_widget = Paragraph::new(loss_text)
         .block(Block::default().borders(Borders::ALL).title("Packet Loss"));
     frame.render_widget(loss_widget, area);
@@ -713,10 +1171,17 @@ fn render_replay_table(
     .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow)));
     let header = Row::new(header_cells).height(1);
 
+    // Targets removed (or not yet added) at this point of the session are hidden
+    let visible: Vec<(&Target, &TargetStats)> = targets
+        .iter()
+        .zip(stats.iter())
+        .filter(|(target, _)| target.active)
+        .collect();
+
     // Calculate row height based on available space
     let table_inner = Block::default().borders(Borders::ALL).inner(area);
     let header_height = 1u16;
-    let num_targets = targets.len() as u16;
+    let num_targets = visible.len() as u16;
     let available_height = table_inner.height.saturating_sub(header_height);
     let row_height = if num_targets > 0 {
         (available_height / (num_targets * 2)).max(1)
@@ -724,19 +1189,12 @@ fn render_replay_table(
         1
     };
 
-    let rows: Vec<Row> = targets
+    let rows: Vec<Row> = visible
         .iter()
-        .zip(stats.iter())
         .enumerate()
         .flat_map(|(idx, (target, stats))| {
             let is_selected = idx == selected;
-            create_target_rows(
-                target.name.as_str(),
-                &target.addr.to_string(),
-                stats,
-                is_selected,
-                row_height,
-            )
+            create_target_rows(target, stats, is_selected, row_height)
         })
         .collect();
 
@@ -760,11 +1218,12 @@ fn render_replay_table(
     frame.render_widget(table, area);
 
     // Render sparklines
-    render_replay_sparklines(frame, area, stats);
+    let visible_stats: Vec<&TargetStats> = visible.iter().map(|(_, stats)| *stats).collect();
+    render_replay_sparklines(frame, area, &visible_stats);
 }
 
 /// Renders sparklines for replay mode.
-fn render_replay_sparklines(frame: &mut Frame, area: Rect, stats: &[TargetStats]) {
+fn render_replay_sparklines(frame: &mut Frame, area: Rect, stats: &[&TargetStats]) {
     let table_inner = Block::default().borders(Borders::ALL).inner(area);
     let header_height = 1u16;
     let num_targets = stats.len() as u16;
@@ -808,6 +1267,7 @@ fn render_replay_sparklines(frame: &mut Frame, area: Rect, stats: &[TargetStats]
                 .style(Style::default().fg(Color::Cyan));
 
             frame.render_widget(sparkline, sparkline_area);
+            render_pause_gaps(frame, sparkline_area, stat);
         }
     }
 }